@@ -0,0 +1,189 @@
+use crate::formats::PasswordFn;
+use crate::otpauth_uri::OtpType;
+use crate::totp_token::Account;
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+// Key slots unlocked by a password, others are biometric
+const PASSWORD_SLOT: u32 = 1;
+
+/// Aegis backup file, `db` is the database object or, when encrypted, its Base-64 ciphertext
+///
+/// https://github.com/beemdevelopment/Aegis/blob/master/docs/vault.md
+#[derive(Debug, Deserialize, Serialize)]
+struct Backup<D> {
+    version: u32,
+    header: Header,
+    db: D,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Header {
+    slots: Option<Vec<Slot>>,
+    params: Option<KeyParams>,
+}
+
+/// A copy of the master key encrypted with a key derived from the password by scrypt
+#[derive(Debug, Deserialize, Serialize)]
+struct Slot {
+    #[serde(rename = "type")]
+    slot_type: u32,
+    key: String,
+    key_params: KeyParams,
+    n: Option<u64>,
+    r: Option<u32>,
+    p: Option<u32>,
+    salt: Option<String>,
+}
+
+/// AES-256-GCM nonce and tag, hex encoded
+#[derive(Debug, Deserialize, Serialize)]
+struct KeyParams {
+    nonce: String,
+    tag: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Database {
+    version: u32,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    #[serde(rename = "type")]
+    entry_type: String,
+    uuid: String,
+    name: String,
+    issuer: String,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    favorite: bool,
+    icon: Option<String>,
+    info: Info,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Info {
+    secret: String,
+    algo: String,
+    digits: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    period: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counter: Option<u64>,
+}
+
+/// Read the accounts of a plain or encrypted Aegis backup
+pub fn import(bytes: &[u8], password: PasswordFn) -> Result<Vec<Account>, Box<dyn Error>> {
+    let backup: Backup<Value> = serde_json::from_slice(bytes).map_err(|e| format!("not an Aegis backup: {e}"))?;
+    let db = match (&backup.db, backup.header.slots, backup.header.params) {
+        (Value::Object(_), _, _) => backup.db,
+        (Value::String(ciphertext), Some(slots), Some(params)) => {
+            let master_key = master_key(&slots, &password("enter Aegis backup password:")?)?;
+            let plaintext = decrypt(&master_key, &params, &general_purpose::STANDARD.decode(ciphertext)?)
+                .map_err(|_| "Aegis database decryption failed")?;
+            serde_json::from_slice(&plaintext)?
+        }
+        _ => return Err("Aegis backup has neither a database nor the key slots to decrypt it".into()),
+    };
+    let db: Database = serde_json::from_value(db).map_err(|e| format!("invalid Aegis database: {e}"))?;
+
+    let mut accounts = vec![];
+    for entry in db.entries {
+        let otp_type = match entry.entry_type.as_str() {
+            "totp" => OtpType::Totp,
+            "hotp" => OtpType::Hotp,
+            "steam" => OtpType::Steam,
+            other => {
+                eprintln!("Skipping Aegis entry {}, unsupported type `{other}`", entry.name);
+                continue;
+            }
+        };
+        accounts.push(Account {
+            secret: entry.info.secret,
+            issuer: entry.issuer,
+            account: entry.name,
+            sha: entry.info.algo.parse()?,
+            digits: entry.info.digits,
+            period: entry.info.period.unwrap_or(30),
+            otp_type,
+            counter: entry.info.counter.unwrap_or_default(),
+        });
+    }
+    Ok(accounts)
+}
+
+/// A plain Aegis backup of the accounts
+pub fn export(accounts: &[&Account]) -> Result<String, Box<dyn Error>> {
+    let entries = accounts
+        .iter()
+        .map(|account| Entry {
+            entry_type: match account.otp_type {
+                OtpType::Totp => "totp".into(),
+                OtpType::Hotp => "hotp".into(),
+                OtpType::Steam => "steam".into(),
+            },
+            uuid: uuid::Uuid::new_v4().to_string(),
+            name: account.account.clone(),
+            issuer: account.issuer.clone(),
+            note: String::new(),
+            favorite: false,
+            icon: None,
+            info: Info {
+                secret: account.secret.clone(),
+                algo: account.sha.to_string(),
+                digits: account.digits,
+                period: (account.otp_type != OtpType::Hotp).then_some(account.period),
+                counter: (account.otp_type == OtpType::Hotp).then_some(account.counter),
+            },
+        })
+        .collect();
+
+    let backup = Backup {
+        version: 1,
+        header: Header::default(),
+        db: Database { version: 2, entries },
+    };
+    Ok(serde_json::to_string_pretty(&backup)?)
+}
+
+/// Unlock the master key with the first password slot the password opens
+fn master_key(slots: &[Slot], password: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    for slot in slots.iter().filter(|slot| slot.slot_type == PASSWORD_SLOT) {
+        let (Some(n), Some(r), Some(p), Some(salt)) = (slot.n, slot.r, slot.p, &slot.salt) else {
+            continue;
+        };
+        if !n.is_power_of_two() {
+            return Err(format!("invalid Aegis scrypt parameter n = {n}").into());
+        }
+        let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p, 32)
+            .map_err(|e| format!("invalid Aegis scrypt parameters: {e}"))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(password.as_bytes(), &hex::decode(salt)?, &params, &mut key)
+            .map_err(|e| format!("scrypt: {e}"))?;
+
+        if let Ok(master_key) = decrypt(&key, &slot.key_params, &hex::decode(&slot.key)?) {
+            return Ok(master_key);
+        }
+    }
+    Err("Aegis backup decryption failed, wrong password?".into())
+}
+
+/// AES-256-GCM with the nonce and tag kept apart from the ciphertext
+fn decrypt(key: &[u8], params: &KeyParams, ciphertext: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let nonce = hex::decode(&params.nonce)?;
+    if key.len() != 32 || nonce.len() != 12 {
+        return Err("invalid AES-GCM key or nonce".into());
+    }
+    let mut message = ciphertext.to_vec();
+    message.extend(hex::decode(&params.tag)?);
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(&nonce), message.as_slice())
+        .map_err(|_| "AES-GCM decryption failed".into())
+}
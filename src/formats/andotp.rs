@@ -0,0 +1,89 @@
+use crate::formats::PasswordFn;
+use crate::otpauth_uri::{split_label, OtpType};
+use crate::totp_token::Account;
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use serde::Deserialize;
+use sha1::Sha1;
+use std::error::Error;
+
+// Encrypted backups start with the PBKDF2 iteration count, salt and IV
+const ITERATIONS_LEN: usize = 4;
+const SALT_LEN: usize = 12;
+const IV_LEN: usize = 12;
+
+/// An andOTP backup entry, older versions put "ISSUER - ACCOUNT" in the label and have no issuer
+#[derive(Debug, Deserialize)]
+struct Entry {
+    secret: String,
+    #[serde(default)]
+    issuer: String,
+    #[serde(default)]
+    label: String,
+    digits: Option<u32>,
+    #[serde(rename = "type")]
+    entry_type: Option<String>,
+    algorithm: Option<String>,
+    period: Option<u64>,
+    counter: Option<u64>,
+}
+
+/// Read the accounts of a plain or encrypted (`.json.aes`) andOTP backup
+pub fn import(bytes: &[u8], password: PasswordFn) -> Result<Vec<Account>, Box<dyn Error>> {
+    let plaintext = match bytes.trim_ascii_start().starts_with(b"[") {
+        true => bytes.to_vec(),
+        false => decrypt(bytes, &password("enter andOTP backup password:")?)?,
+    };
+    let entries: Vec<Entry> = serde_json::from_slice(&plaintext).map_err(|e| format!("not an andOTP backup: {e}"))?;
+
+    let mut accounts = vec![];
+    for entry in entries {
+        let otp_type = match entry.entry_type.as_deref().unwrap_or("TOTP") {
+            "TOTP" => OtpType::Totp,
+            "HOTP" => OtpType::Hotp,
+            "STEAM" => OtpType::Steam,
+            other => {
+                eprintln!("Skipping andOTP entry {}, unsupported type `{other}`", entry.label);
+                continue;
+            }
+        };
+        let (issuer, account) = match entry.issuer.is_empty() {
+            true => match entry.label.split_once(" - ") {
+                Some((issuer, account)) => (issuer.to_string(), account.to_string()),
+                None => {
+                    let (issuer, account) = split_label(&entry.label);
+                    (issuer.unwrap_or_default(), account)
+                }
+            },
+            false => (entry.issuer, entry.label),
+        };
+        accounts.push(Account {
+            secret: entry.secret,
+            issuer,
+            account,
+            sha: entry.algorithm.as_deref().unwrap_or("SHA1").parse()?,
+            digits: entry.digits.unwrap_or(6),
+            period: entry.period.unwrap_or(30),
+            otp_type,
+            counter: entry.counter.unwrap_or_default(),
+        });
+    }
+    Ok(accounts)
+}
+
+/// Big-endian PBKDF2-HMAC-SHA1 iteration count, salt, IV, then the AES-256-GCM ciphertext and tag
+fn decrypt(bytes: &[u8], password: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if bytes.len() < ITERATIONS_LEN + SALT_LEN + IV_LEN + 16 {
+        return Err("not an andOTP backup, too short to be encrypted".into());
+    }
+    let (iterations, rest) = bytes.split_at(ITERATIONS_LEN);
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (iv, ciphertext) = rest.split_at(IV_LEN);
+    let iterations = u32::from_be_bytes(iterations.try_into()?);
+
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha1>(password.as_bytes(), salt, iterations, &mut key);
+    Aes256Gcm::new(&key.into())
+        .decrypt(Nonce::from_slice(iv), ciphertext)
+        .map_err(|_| "andOTP backup decryption failed, wrong password?".into())
+}
@@ -0,0 +1,130 @@
+use crate::formats::PasswordFn;
+use crate::otpauth_uri::OtpType;
+use crate::totp_token::{Account, Algorithm};
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use argon2::{Argon2, Params, Version};
+use serde::Deserialize;
+use sha1::Sha1;
+use std::error::Error;
+
+// Encrypted backups start with a header, then the salt and IV
+const HEADER: &[u8] = b"AUTHENTICATORPRO";
+const SALT_LEN: usize = 16;
+const IV_LEN: usize = 12;
+
+// Backups of versions before 1.15 use PBKDF2 and AES-256-CBC
+const LEGACY_HEADER: &[u8] = b"AuthenticatorPro";
+const LEGACY_SALT_LEN: usize = 20;
+const LEGACY_IV_LEN: usize = 16;
+const LEGACY_ITERATIONS: u32 = 64000;
+
+// Authenticator types and algorithms
+const HOTP: u32 = 1;
+const TOTP: u32 = 2;
+const STEAM: u32 = 4;
+const ALGORITHMS: [Algorithm; 3] = [Algorithm::SHA1, Algorithm::SHA256, Algorithm::SHA512];
+
+/// Authenticator Pro (Stratum) backup, categories and icons are ignored
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Backup {
+    authenticators: Vec<Authenticator>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Authenticator {
+    #[serde(rename = "Type")]
+    auth_type: u32,
+    #[serde(default)]
+    issuer: String,
+    username: Option<String>,
+    secret: String,
+    algorithm: usize,
+    digits: u32,
+    period: u64,
+    #[serde(default)]
+    counter: u64,
+}
+
+/// Read the accounts of a plain JSON or encrypted `.authpro` Authenticator Pro backup
+pub fn import(bytes: &[u8], password: PasswordFn) -> Result<Vec<Account>, Box<dyn Error>> {
+    let plaintext;
+    let json = if bytes.starts_with(HEADER) || bytes.starts_with(LEGACY_HEADER) {
+        plaintext = decrypt(bytes, &password("enter Authenticator Pro backup password:")?)?;
+        &plaintext
+    } else {
+        bytes
+    };
+    let backup: Backup = serde_json::from_slice(json).map_err(|e| format!("not an Authenticator Pro backup: {e}"))?;
+
+    let mut accounts = vec![];
+    for authenticator in backup.authenticators {
+        let name = authenticator.username.unwrap_or_default();
+        let otp_type = match authenticator.auth_type {
+            TOTP => OtpType::Totp,
+            HOTP => OtpType::Hotp,
+            STEAM => OtpType::Steam,
+            other => {
+                let other = match other {
+                    3 => "mobile-otp".into(),
+                    5 => "yandex".into(),
+                    other => other.to_string(),
+                };
+                eprintln!("Skipping Authenticator Pro entry {name}, unsupported type `{other}`");
+                continue;
+            }
+        };
+        let sha = ALGORITHMS.get(authenticator.algorithm).ok_or_else(|| {
+            format!(
+                "Authenticator Pro entry {name}: unsupported algorithm {}",
+                authenticator.algorithm
+            )
+        })?;
+        accounts.push(Account {
+            secret: authenticator.secret,
+            issuer: authenticator.issuer,
+            account: name,
+            sha: *sha,
+            digits: authenticator.digits,
+            period: authenticator.period,
+            otp_type,
+            counter: authenticator.counter,
+        });
+    }
+    Ok(accounts)
+}
+
+/// Argon2id and AES-256-GCM, or PBKDF2-HMAC-SHA1 and AES-256-CBC for legacy backups
+fn decrypt(bytes: &[u8], password: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let failed = "Authenticator Pro backup decryption failed, wrong password?";
+    let mut key = [0u8; 32];
+    if let Some(rest) = bytes.strip_prefix(HEADER) {
+        if rest.len() < SALT_LEN + IV_LEN {
+            return Err("Authenticator Pro backup is truncated".into());
+        }
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (iv, ciphertext) = rest.split_at(IV_LEN);
+        let params = Params::new(64 * 1024, 3, 4, Some(32)).map_err(|e| format!("argon2id: {e}"))?;
+        Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), salt, &mut key)
+            .map_err(|e| format!("argon2id: {e}"))?;
+        Ok(Aes256Gcm::new(&key.into())
+            .decrypt(Nonce::from_slice(iv), ciphertext)
+            .map_err(|_| failed)?)
+    } else {
+        let rest = &bytes[LEGACY_HEADER.len()..];
+        if rest.len() < LEGACY_SALT_LEN + LEGACY_IV_LEN {
+            return Err("Authenticator Pro backup is truncated".into());
+        }
+        let (salt, rest) = rest.split_at(LEGACY_SALT_LEN);
+        let (iv, ciphertext) = rest.split_at(LEGACY_IV_LEN);
+        pbkdf2::pbkdf2_hmac::<Sha1>(password.as_bytes(), salt, LEGACY_ITERATIONS, &mut key);
+        Ok(cbc::Decryptor::<aes::Aes256>::new_from_slices(&key, iv)
+            .map_err(|_| "invalid Authenticator Pro IV")?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .map_err(|_| failed)?)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::otpauth_uri::OtpType;
+use crate::totp_token::{self, Account, Algorithm};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Bitwarden item type of logins, the only items holding TOTP
+const LOGIN: u32 = 1;
+
+/// Unencrypted Bitwarden JSON export
+#[derive(Debug, Deserialize, Serialize)]
+struct Export {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    folders: Vec<serde_json::Value>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    #[serde(default)]
+    id: String,
+    organization_id: Option<String>,
+    folder_id: Option<String>,
+    #[serde(rename = "type")]
+    item_type: u32,
+    #[serde(default)]
+    reprompt: u32,
+    name: String,
+    notes: Option<String>,
+    #[serde(default)]
+    favorite: bool,
+    login: Option<Login>,
+    collection_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Login {
+    #[serde(default)]
+    uris: Vec<serde_json::Value>,
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+}
+
+/// Read the accounts of an unencrypted Bitwarden JSON export
+///
+/// `login.totp` holds an otpauth URI, a bare Base-32 secret or a `steam://` Steam Guard secret,
+/// the item name stands in for a missing issuer and the user name for a missing account name
+pub fn import(bytes: &[u8]) -> Result<Vec<Account>, Box<dyn Error>> {
+    let export: Export = serde_json::from_slice(bytes).map_err(|e| format!("not a Bitwarden export: {e}"))?;
+    if export.encrypted {
+        return Err("encrypted Bitwarden exports are not supported, export as unencrypted JSON".into());
+    }
+
+    let mut accounts = vec![];
+    for item in export.items.into_iter().filter(|item| item.item_type == LOGIN) {
+        let Some(login) = item.login else { continue };
+        let totp = login.totp.unwrap_or_default();
+        let totp = totp.trim();
+        let mut item_accounts = if totp.is_empty() {
+            continue;
+        } else if totp.starts_with("otpauth://") {
+            totp_token::get_accounts(totp).map_err(|e| format!("Bitwarden item `{}`: {e}", item.name))?
+        } else {
+            let (otp_type, secret) = match totp.strip_prefix("steam://") {
+                Some(secret) => (OtpType::Steam, secret),
+                None => (OtpType::Totp, totp),
+            };
+            vec![Account {
+                secret: secret.replace(' ', "").to_uppercase(),
+                issuer: String::new(),
+                account: String::new(),
+                sha: Algorithm::SHA1,
+                digits: match otp_type {
+                    OtpType::Steam => totp_token::STEAM_DIGITS,
+                    _ => 6,
+                },
+                period: 30,
+                otp_type,
+                counter: 0,
+            }]
+        };
+
+        let username = login.username.unwrap_or_default();
+        for account in &mut item_accounts {
+            if account.account.is_empty() {
+                account.account = match username.is_empty() && !account.issuer.is_empty() {
+                    true => item.name.clone(),
+                    false => username.clone(),
+                };
+            }
+            if account.issuer.is_empty() {
+                account.issuer = item.name.clone();
+            }
+        }
+        accounts.extend(item_accounts);
+    }
+    Ok(accounts)
+}
+
+/// An unencrypted Bitwarden JSON export with a login item per account
+pub fn export(accounts: &[&Account]) -> Result<String, Box<dyn Error>> {
+    let items = accounts
+        .iter()
+        .map(|account| Item {
+            id: uuid::Uuid::new_v4().to_string(),
+            organization_id: None,
+            folder_id: None,
+            item_type: LOGIN,
+            reprompt: 0,
+            name: match account.issuer.is_empty() {
+                true => account.account.clone(),
+                false => account.issuer.clone(),
+            },
+            notes: None,
+            favorite: false,
+            login: Some(Login {
+                uris: vec![],
+                username: Some(account.account.clone()),
+                password: None,
+                totp: Some(match account.otp_type {
+                    OtpType::Steam => format!("steam://{}", account.secret),
+                    _ => account.uri(),
+                }),
+            }),
+            collection_ids: None,
+        })
+        .collect();
+
+    let export = Export {
+        encrypted: false,
+        folders: vec![],
+        items,
+    };
+    Ok(serde_json::to_string_pretty(&export)?)
+}
@@ -0,0 +1,210 @@
+use crate::otpauth_uri::OtpType;
+use crate::totp_token::{self, Account, Algorithm};
+use std::error::Error;
+
+/// Account fields a CSV column can be mapped to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Issuer,
+    Account,
+    Secret,
+    Algorithm,
+    Digits,
+    Period,
+    Otpauth,
+}
+
+impl Field {
+    const ALL: [Field; 7] = [
+        Field::Issuer,
+        Field::Account,
+        Field::Secret,
+        Field::Algorithm,
+        Field::Digits,
+        Field::Period,
+        Field::Otpauth,
+    ];
+
+    /// The header written by --export=csv
+    fn name(self) -> &'static str {
+        match self {
+            Field::Issuer => "issuer",
+            Field::Account => "account",
+            Field::Secret => "secret",
+            Field::Algorithm => "algorithm",
+            Field::Digits => "digits",
+            Field::Period => "period",
+            Field::Otpauth => "otpauth",
+        }
+    }
+
+    /// Headers recognized without a mapping, in order of preference, matched ignoring case.
+    /// 1Password, Proton Pass, LastPass and Bitwarden CSV exports are covered
+    fn aliases(self) -> &'static [&'static str] {
+        match self {
+            Field::Issuer => &["issuer", "title", "name", "service"],
+            Field::Account => &["account", "username", "login_username", "user name", "login", "email"],
+            Field::Secret => &["secret", "totp secret", "otp secret", "seed"],
+            Field::Algorithm => &["algorithm", "algo"],
+            Field::Digits => &["digits"],
+            Field::Period => &["period", "interval", "step"],
+            Field::Otpauth => &["otpauth", "otpauth uri", "uri", "totp", "login_totp", "otp"],
+        }
+    }
+}
+
+/// Parse a `--csv-column FIELD=HEADER` mapping, e.g. "secret=2FA Key"
+pub fn parse_column(mapping: &str) -> Result<(Field, String), String> {
+    let (field, header) = mapping
+        .split_once('=')
+        .ok_or_else(|| format!("expected FIELD=HEADER, got `{mapping}`"))?;
+    let field = Field::ALL
+        .into_iter()
+        .find(|f| f.name().eq_ignore_ascii_case(field.trim()))
+        .ok_or_else(|| {
+            let names: Vec<_> = Field::ALL.iter().map(|f| f.name()).collect();
+            format!("unknown field `{field}`, expected one of {}", names.join(", "))
+        })?;
+    Ok((field, header.trim().into()))
+}
+
+/// Read the accounts of a CSV file with a header row
+///
+/// Columns are found by their header, `columns` maps a field to a header explicitly.
+/// A row's otpauth column holds an otpauth URI or a bare Base-32 secret, the other
+/// columns fill in what the URI leaves out. Rows without a secret are skipped,
+/// rows that cannot be read are reported by line number
+pub fn import(bytes: &[u8], columns: &[(Field, String)]) -> Result<Vec<Account>, Box<dyn Error>> {
+    let mut reader = ::csv::ReaderBuilder::new().trim(::csv::Trim::All).from_reader(bytes);
+    let headers = reader.headers().map_err(|e| format!("CSV header: {e}"))?.clone();
+
+    // The column indexes of each field, the first non-empty value is used
+    let mut indexes = vec![];
+    for field in Field::ALL {
+        let found: Vec<usize> = match columns.iter().find(|(f, _)| *f == field) {
+            Some((_, header)) => vec![headers
+                .iter()
+                .position(|h| h.eq_ignore_ascii_case(header))
+                .ok_or_else(|| format!("CSV has no column `{header}`"))?],
+            None => field
+                .aliases()
+                .iter()
+                .flat_map(|alias| headers.iter().position(|h| h.eq_ignore_ascii_case(alias)))
+                .collect(),
+        };
+        indexes.push((field, found));
+    }
+    if indexes
+        .iter()
+        .all(|(field, found)| found.is_empty() || !matches!(field, Field::Secret | Field::Otpauth))
+    {
+        return Err("CSV has no secret or otpauth column, map one with --csv-column".into());
+    }
+
+    let mut accounts = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|e| match e.position() {
+            Some(position) => format!("CSV line {}: {e}", position.line()),
+            None => format!("CSV: {e}"),
+        })?;
+        let line = record.position().map(|position| position.line()).unwrap_or_default();
+        // The header and value of the column a field is read from, ("", "") when every column is empty
+        let column = |field: Field| {
+            indexes
+                .iter()
+                .filter(|(f, _)| *f == field)
+                .flat_map(|(_, found)| found)
+                .filter_map(|&index| Some((&headers[index], record.get(index)?)))
+                .find(|(_, value)| !value.is_empty())
+                .unwrap_or_default()
+        };
+        let row_accounts = row(column).map_err(|e| format!("CSV line {line}, {e}"))?;
+        accounts.extend(row_accounts);
+    }
+    Ok(accounts)
+}
+
+/// The accounts of one row, `column` returns the header and value a field is read from
+///
+/// Errors name the column, e.g. "column `Period`: invalid period `x`", secrets are never part of them
+fn row<'a>(column: impl Fn(Field) -> (&'a str, &'a str)) -> Result<Vec<Account>, Box<dyn Error>> {
+    let value = |field| column(field).1;
+    let in_column = |field, e: &dyn std::fmt::Display| format!("column `{}`: {e}", column(field).0);
+    let otpauth = value(Field::Otpauth);
+    let mut accounts = match otpauth.starts_with("otpauth") {
+        true => totp_token::get_accounts(otpauth).map_err(|e| in_column(Field::Otpauth, &e))?,
+        false => {
+            let (field, secret) = match otpauth.is_empty() {
+                true => (Field::Secret, value(Field::Secret)),
+                false => (Field::Otpauth, otpauth),
+            };
+            if secret.is_empty() {
+                return Ok(vec![]);
+            }
+            let secret = secret.replace([' ', '-'], "").to_uppercase();
+            if base32::decode(
+                base32::Alphabet::RFC4648 { padding: false },
+                secret.trim_end_matches('='),
+            )
+            .is_none()
+            {
+                return Err(in_column(field, &"invalid Base-32 secret").into());
+            }
+            let sha = match value(Field::Algorithm) {
+                "" => Algorithm::SHA1,
+                sha => sha.parse().map_err(|e| in_column(Field::Algorithm, &e))?,
+            };
+            let digits = match value(Field::Digits) {
+                "" => 6,
+                digits => totp_token::parse_digits(digits).map_err(|e| in_column(Field::Digits, &e))?,
+            };
+            let period = match value(Field::Period) {
+                "" => 30,
+                period => period
+                    .parse()
+                    .map_err(|_| in_column(Field::Period, &format!("invalid period `{period}`")))?,
+            };
+            vec![Account {
+                secret,
+                issuer: String::new(),
+                account: String::new(),
+                sha,
+                digits,
+                period,
+                otp_type: OtpType::Totp,
+                counter: 0,
+            }]
+        }
+    };
+
+    for account in &mut accounts {
+        if account.issuer.is_empty() {
+            account.issuer = value(Field::Issuer).into();
+        }
+        if account.account.is_empty() {
+            account.account = value(Field::Account).into();
+        }
+    }
+    Ok(accounts)
+}
+
+/// CSV with a header row and a row per account, the otpauth column keeps HOTP counters
+pub fn export(accounts: &[&Account]) -> Result<String, Box<dyn Error>> {
+    let mut writer = ::csv::WriterBuilder::new()
+        .terminator(::csv::Terminator::Any(b'\n'))
+        .from_writer(vec![]);
+    writer.write_record(Field::ALL.map(Field::name))?;
+    for account in accounts {
+        writer.write_record([
+            account.issuer.as_str(),
+            account.account.as_str(),
+            account.secret.as_str(),
+            &account.sha.to_string(),
+            &account.digits.to_string(),
+            &account.period.to_string(),
+            &account.uri(),
+        ])?;
+    }
+    let csv = String::from_utf8(writer.into_inner()?)?;
+    Ok(csv.trim_end_matches('\n').into())
+}
@@ -0,0 +1,128 @@
+use crate::formats::PasswordFn;
+use crate::otpauth_uri::OtpAuthUri;
+use crate::totp_token::{self, Account};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use chacha20::cipher::consts::U10;
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20::ChaCha20;
+use poly1305::universal_hash::KeyInit;
+use poly1305::Poly1305;
+use serde::Deserialize;
+use std::error::Error;
+
+// libsodium secretstream tag of the last message
+const TAG_FINAL: u8 = 3;
+
+/// Encrypted Ente Auth export, the plaintext holds an otpauth URI per line
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    version: u32,
+    kdf_params: KdfParams,
+    encrypted_data: String,
+    encryption_nonce: String,
+}
+
+/// libsodium crypto_pwhash (Argon2id) parameters, `mem_limit` is in bytes
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KdfParams {
+    mem_limit: u32,
+    ops_limit: u32,
+    salt: String,
+}
+
+/// Read the accounts of a plain or encrypted Ente Auth export
+///
+/// Entries in the trash are skipped
+pub fn import(bytes: &[u8], password: PasswordFn) -> Result<Vec<Account>, Box<dyn Error>> {
+    let text = match serde_json::from_slice::<Export>(bytes) {
+        Ok(export) => decrypt(&export, &password("enter Ente Auth export password:")?)?,
+        Err(e) if bytes.trim_ascii_start().starts_with(b"{") => {
+            return Err(format!("not an Ente Auth export: {e}").into());
+        }
+        Err(_) => String::from_utf8(bytes.to_vec()).map_err(|e| format!("not an Ente Auth export: {e}"))?,
+    };
+
+    let mut accounts = vec![];
+    for otpauth in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let uri = OtpAuthUri::parse(otpauth).map_err(|e| format!("not an Ente Auth export: {e}"))?;
+        let trashed = uri
+            .param("codeDisplay")
+            .and_then(|display| serde_json::from_str::<serde_json::Value>(display).ok())
+            .is_some_and(|display| display["trashed"] == true);
+        if trashed {
+            continue;
+        }
+        accounts.extend(totp_token::get_accounts(otpauth)?);
+    }
+    Ok(accounts)
+}
+
+/// Derive the key with Argon2id and open the libsodium secretstream
+fn decrypt(export: &Export, password: &str) -> Result<String, Box<dyn Error>> {
+    if export.version != 1 {
+        return Err(format!("unsupported Ente Auth export version {}", export.version).into());
+    }
+    let kdf = &export.kdf_params;
+    let params = Params::new(kdf.mem_limit / 1024, kdf.ops_limit, 1, Some(32))
+        .map_err(|e| format!("invalid Ente Auth argon2id parameters: {e}"))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(
+            password.as_bytes(),
+            &general_purpose::STANDARD.decode(&kdf.salt)?,
+            &mut key,
+        )
+        .map_err(|e| format!("argon2id: {e}"))?;
+
+    let header = general_purpose::STANDARD.decode(&export.encryption_nonce)?;
+    let ciphertext = general_purpose::STANDARD.decode(&export.encrypted_data)?;
+    let (tag, plaintext) = secretstream_pull(&key, &header, &ciphertext)?;
+    if tag != TAG_FINAL {
+        return Err("Ente Auth export is truncated".into());
+    }
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// The first message of a libsodium crypto_secretstream_xchacha20poly1305 stream and its tag
+///
+/// The header's first 16 bytes and the key give the HChaCha20 subkey, the message is
+/// ChaCha20 with the counter 1 and the header's last 8 bytes as nonce: a block holding
+/// the encrypted tag then the encrypted message, authenticated by Poly1305
+fn secretstream_pull(key: &[u8; 32], header: &[u8], message: &[u8]) -> Result<(u8, Vec<u8>), Box<dyn Error>> {
+    if header.len() != 24 || message.len() < 17 {
+        return Err("invalid Ente Auth secretstream".into());
+    }
+    let subkey = chacha20::hchacha::<U10>(key.into(), header[..16].into());
+    let mut nonce = [0u8; 12];
+    nonce[0] = 1;
+    nonce[4..].copy_from_slice(&header[16..]);
+    let mut cipher = ChaCha20::new(&subkey, &nonce.into());
+
+    let mut poly_key = [0u8; 64];
+    cipher.apply_keystream(&mut poly_key);
+    let mut block = [0u8; 64];
+    block[0] = message[0];
+    cipher.apply_keystream(&mut block);
+    let tag = block[0];
+    block[0] = message[0];
+
+    let (ciphertext, mac) = message[1..].split_at(message.len() - 17);
+    let mut authenticated = block.to_vec();
+    authenticated.extend_from_slice(ciphertext);
+    // libsodium pads with (0x10 - 64 + mlen) & 0xf zero bytes, mlen % 16 rather than up to a multiple of 16
+    authenticated.resize(authenticated.len() + ciphertext.len() % 16, 0);
+    authenticated.extend_from_slice(&0u64.to_le_bytes());
+    authenticated.extend_from_slice(&((64 + ciphertext.len()) as u64).to_le_bytes());
+    let expected = Poly1305::new(poly_key[..32].into()).compute_unpadded(&authenticated);
+    if expected.as_slice() != mac {
+        return Err("Ente Auth export decryption failed, wrong password?".into());
+    }
+
+    let mut plaintext = ciphertext.to_vec();
+    cipher.seek(128u64);
+    cipher.apply_keystream(&mut plaintext);
+    Ok((tag, plaintext))
+}
@@ -0,0 +1,56 @@
+use crate::otpauth_uri::OtpType;
+use crate::totp_token::Account;
+use serde::Deserialize;
+use std::error::Error;
+
+/// FreeOTP+ JSON export
+#[derive(Debug, Deserialize)]
+struct Export {
+    tokens: Vec<Token>,
+}
+
+/// A FreeOTP+ token, the secret is the raw key as Java's signed bytes
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Token {
+    secret: Vec<i8>,
+    #[serde(default)]
+    issuer_ext: String,
+    #[serde(default)]
+    label: String,
+    algo: Option<String>,
+    digits: Option<u32>,
+    period: Option<u64>,
+    counter: Option<u64>,
+    #[serde(rename = "type")]
+    token_type: Option<String>,
+}
+
+/// Read the accounts of a FreeOTP+ JSON export
+pub fn import(bytes: &[u8]) -> Result<Vec<Account>, Box<dyn Error>> {
+    let export: Export = serde_json::from_slice(bytes).map_err(|e| format!("not a FreeOTP+ export: {e}"))?;
+
+    let mut accounts = vec![];
+    for token in export.tokens {
+        let otp_type = match token.token_type.as_deref().unwrap_or("TOTP") {
+            "TOTP" => OtpType::Totp,
+            "HOTP" => OtpType::Hotp,
+            other => {
+                eprintln!("Skipping FreeOTP+ token {}, unsupported type `{other}`", token.label);
+                continue;
+            }
+        };
+        let secret: Vec<u8> = token.secret.iter().map(|&b| b as u8).collect();
+        accounts.push(Account {
+            secret: base32::encode(base32::Alphabet::RFC4648 { padding: false }, &secret),
+            issuer: token.issuer_ext,
+            account: token.label,
+            sha: token.algo.as_deref().unwrap_or("SHA1").parse()?,
+            digits: token.digits.unwrap_or(6),
+            period: token.period.unwrap_or(30),
+            otp_type,
+            counter: token.counter.unwrap_or_default(),
+        });
+    }
+    Ok(accounts)
+}
@@ -0,0 +1,575 @@
+use crate::otpauth_uri::{OtpAuthUri, OtpType};
+use crate::totp_token::{self, Account, Algorithm};
+use crate::vault::KdfParams;
+use aes::cipher::{
+    block_padding::Pkcs7, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit, StreamCipher,
+};
+use base64::{engine::general_purpose, Engine as _};
+use chacha20::ChaCha20;
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// KeePass 2.x file signatures and the KDBX 4.0 version
+const SIGNATURE: [u32; 2] = [0x9AA2_D903, 0xB54B_FB67];
+const MAJOR_VERSION: u16 = 4;
+
+// Outer header field ids
+const END_OF_HEADER: u8 = 0;
+const CIPHER_ID: u8 = 2;
+const COMPRESSION_FLAGS: u8 = 3;
+const MASTER_SEED: u8 = 4;
+const ENCRYPTION_IV: u8 = 7;
+const KDF_PARAMETERS: u8 = 11;
+
+// Inner header field ids
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+const INNER_STREAM_CHACHA20: u32 = 3;
+
+const CIPHER_AES256: [u8; 16] = uuid(0x31c1f2e6_bf71_4350_be58_05216afc5aff);
+const CIPHER_CHACHA20: [u8; 16] = uuid(0xd6038a2b_8b6f_4cb5_a524_339a31dbb59a);
+const KDF_AES: [u8; 16] = uuid(0xc9d9f39a_628a_4460_bf74_0d08c18a4fea);
+const KDF_ARGON2D: [u8; 16] = uuid(0xef636ddf_8c29_444b_91f7_a9a403e30a0c);
+const KDF_ARGON2ID: [u8; 16] = uuid(0x9e298b19_56db_4773_b23d_fc3ec6f0a1e6);
+
+// KDBX 4 times are seconds since 0001-01-01 00:00:00 UTC
+const UNIX_EPOCH_SECONDS: u64 = 62_135_596_800;
+
+// The payload is written in HMAC-SHA256 authenticated blocks of up to 1 MiB
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+const fn uuid(value: u128) -> [u8; 16] {
+    value.to_be_bytes()
+}
+
+/// Little-endian reader over the database bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or("KeePass database is truncated")?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn Error>> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    /// Type-length-value header fields by id, up to the end of header field
+    fn fields(&mut self) -> Result<HashMap<u8, &'a [u8]>, Box<dyn Error>> {
+        let mut fields = HashMap::new();
+        loop {
+            let id = self.u8()?;
+            let len = self.u32()? as usize;
+            let data = self.take(len)?;
+            if id == END_OF_HEADER {
+                return Ok(fields);
+            }
+            fields.insert(id, data);
+        }
+    }
+}
+
+/// Read the TOTP and HOTP accounts of a KDBX 4 database
+///
+/// Entries holding an `otp` attribute (otpauth URI or KeeOTP "key=...&step=...")
+/// or the legacy `TOTP Seed` and `TOTP Settings` attributes become accounts,
+/// the entry title and user name fill in a missing issuer and account name
+pub fn import(bytes: &[u8], password: &str, key_file: Option<&[u8]>) -> Result<Vec<Account>, Box<dyn Error>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if [reader.u32()?, reader.u32()?] != SIGNATURE {
+        return Err("not a KeePass database".into());
+    }
+    let (minor, major) = (reader.u16()?, reader.u16()?);
+    if major != MAJOR_VERSION {
+        return Err(format!("KDBX {major}.{minor} databases are not supported, only KDBX 4").into());
+    }
+
+    let fields = reader.fields()?;
+    let header = &bytes[..reader.pos];
+    let field = |id: u8, name: &str| fields.get(&id).copied().ok_or(format!("KeePass header has no {name}"));
+
+    if Sha256::digest(header).as_slice() != reader.take(32)? {
+        return Err("KeePass database header is corrupt".into());
+    }
+    let header_hmac = reader.take(32)?;
+
+    let kdf = variant_dictionary(field(KDF_PARAMETERS, "key derivation parameters")?)?;
+    let transformed = transform_key(&kdf, &composite_key(password, key_file)?)?;
+    let master_seed = field(MASTER_SEED, "master seed")?;
+    let hmac_key = Sha512::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .chain_update([1])
+        .finalize();
+    let mut mac = block_hmac(&hmac_key, u64::MAX);
+    mac.update(header);
+    if mac.verify_slice(header_hmac).is_err() {
+        return Err("KeePass database unlock failed, wrong password or key file?".into());
+    }
+
+    // The HMAC blocks hold the encrypted payload, an empty block ends them
+    let mut ciphertext = vec![];
+    for index in 0.. {
+        let hmac = reader.take(32)?;
+        let len = reader.u32()?;
+        let data = reader.take(len as usize)?;
+        let mut mac = block_hmac(&hmac_key, index);
+        mac.update(&index.to_le_bytes());
+        mac.update(&len.to_le_bytes());
+        mac.update(data);
+        mac.verify_slice(hmac)
+            .map_err(|_| format!("KeePass database block {index} is corrupt"))?;
+        if data.is_empty() {
+            break;
+        }
+        ciphertext.extend_from_slice(data);
+    }
+
+    let master_key = Sha256::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .finalize();
+    let iv = field(ENCRYPTION_IV, "encryption IV")?;
+    let cipher: [u8; 16] = field(CIPHER_ID, "cipher")?.try_into()?;
+    let mut payload = match cipher {
+        CIPHER_AES256 => cbc::Decryptor::<aes::Aes256>::new_from_slices(&master_key, iv)
+            .map_err(|_| "invalid KeePass encryption IV")?
+            .decrypt_padded_vec_mut::<Pkcs7>(&ciphertext)
+            .map_err(|_| "KeePass database decryption failed")?,
+        CIPHER_CHACHA20 => {
+            let mut cipher = ChaCha20::new_from_slices(&master_key, iv).map_err(|_| "invalid KeePass encryption IV")?;
+            cipher.apply_keystream(&mut ciphertext);
+            ciphertext
+        }
+        _ => return Err("KeePass database cipher is not supported, only AES-256 and ChaCha20".into()),
+    };
+    match field(COMPRESSION_FLAGS, "compression flags")? {
+        [0, 0, 0, 0] => {}
+        [1, 0, 0, 0] => {
+            let mut decompressed = vec![];
+            GzDecoder::new(payload.as_slice()).read_to_end(&mut decompressed)?;
+            payload = decompressed;
+        }
+        _ => return Err("KeePass database compression is not supported".into()),
+    }
+
+    // The inner header keys the stream protecting values marked Protected="True"
+    let mut inner = Reader {
+        bytes: &payload,
+        pos: 0,
+    };
+    let inner_fields = inner.fields()?;
+    let stream_id = inner_fields
+        .get(&INNER_STREAM_ID)
+        .map(|&id| <[u8; 4]>::try_from(id).map(u32::from_le_bytes));
+    if stream_id.transpose()? != Some(INNER_STREAM_CHACHA20) {
+        return Err("KeePass inner stream is not supported, only ChaCha20".into());
+    }
+    let mut stream = inner_stream(
+        inner_fields
+            .get(&INNER_STREAM_KEY)
+            .ok_or("KeePass inner stream has no key")?,
+    );
+
+    let xml = std::str::from_utf8(&payload[inner.pos..])?;
+    let document = roxmltree::Document::parse(xml).map_err(|e| format!("invalid KeePass XML: {e}"))?;
+
+    // Protected values are unmasked in document order, including those of entry history
+    let mut values = HashMap::new();
+    for node in document.descendants().filter(|node| node.has_tag_name("Value")) {
+        let mut value = node.text().unwrap_or_default().as_bytes().to_vec();
+        if node.attribute("Protected") == Some("True") {
+            value = general_purpose::STANDARD.decode(&value)?;
+            stream.apply_keystream(&mut value);
+        }
+        values.insert(node.id(), String::from_utf8(value)?);
+    }
+
+    let mut accounts = vec![];
+    let entries = document
+        .descendants()
+        .filter(|node| node.has_tag_name("Entry"))
+        .filter(|node| !node.ancestors().any(|ancestor| ancestor.has_tag_name("History")));
+    for entry in entries {
+        let strings: HashMap<_, _> = entry
+            .children()
+            .filter(|node| node.has_tag_name("String"))
+            .filter_map(|node| {
+                let key = node.children().find(|child| child.has_tag_name("Key"))?.text()?;
+                let value = node.children().find(|child| child.has_tag_name("Value"))?;
+                Some((key, values.get(&value.id())?.as_str()))
+            })
+            .collect();
+        let title = strings.get("Title").copied().unwrap_or_default();
+        accounts.extend(entry_accounts(&strings).map_err(|e| format!("KeePass entry `{title}`: {e}"))?);
+    }
+    Ok(accounts)
+}
+
+/// Write a KDBX 4 database, AES-256 encrypted with an Argon2id key, one entry per account
+///
+/// Entries are titled by the issuer and hold the otpauth URI in the `otp` attribute KeePassXC reads
+pub fn export(accounts: &[&Account], password: &str, kdf: &KdfParams) -> Result<Vec<u8>, Box<dyn Error>> {
+    let master_seed = random::<32>();
+    let iv = random::<16>();
+    let stream_key = random::<64>();
+    let salt = general_purpose::STANDARD.decode(&kdf.salt)?;
+
+    // VariantDictionary version 1.0 of (type, name, value), 0x04 u32, 0x05 u64, 0x42 bytes
+    let argon2 = [
+        (0x42, "$UUID", KDF_ARGON2ID.to_vec()),
+        (0x42, "S", salt),
+        (0x04, "P", kdf.parallelism.to_le_bytes().to_vec()),
+        (0x05, "M", (kdf.memory_kib as u64 * 1024).to_le_bytes().to_vec()),
+        (0x05, "I", (kdf.iterations as u64).to_le_bytes().to_vec()),
+        (0x04, "V", 0x13u32.to_le_bytes().to_vec()),
+    ];
+    let mut kdf_parameters = vec![0x00, 0x01];
+    for (kind, name, value) in &argon2 {
+        kdf_parameters.push(*kind);
+        kdf_parameters.extend((name.len() as u32).to_le_bytes());
+        kdf_parameters.extend(name.as_bytes());
+        kdf_parameters.extend((value.len() as u32).to_le_bytes());
+        kdf_parameters.extend(value);
+    }
+    kdf_parameters.push(0);
+
+    let mut header = vec![];
+    for signature in SIGNATURE {
+        header.extend(signature.to_le_bytes());
+    }
+    header.extend(0u16.to_le_bytes());
+    header.extend(MAJOR_VERSION.to_le_bytes());
+    for (id, data) in [
+        (CIPHER_ID, &CIPHER_AES256[..]),
+        (COMPRESSION_FLAGS, &1u32.to_le_bytes()),
+        (MASTER_SEED, &master_seed),
+        (ENCRYPTION_IV, &iv),
+        (KDF_PARAMETERS, &kdf_parameters),
+        (END_OF_HEADER, b"\r\n\r\n"),
+    ] {
+        write_field(&mut header, id, data);
+    }
+
+    let argon2 = argon2
+        .into_iter()
+        .map(|(_, name, value)| (name.to_string(), value))
+        .collect();
+    let transformed = transform_key(&argon2, &composite_key(password, None)?)?;
+    let master_key = Sha256::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .finalize();
+    let hmac_key = Sha512::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .chain_update([1])
+        .finalize();
+
+    let mut payload = vec![];
+    write_field(&mut payload, INNER_STREAM_ID, &INNER_STREAM_CHACHA20.to_le_bytes());
+    write_field(&mut payload, INNER_STREAM_KEY, &stream_key);
+    write_field(&mut payload, END_OF_HEADER, &[]);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    payload.extend(to_xml(accounts, &mut inner_stream(&stream_key), now).as_bytes());
+
+    let mut gzip = GzEncoder::new(vec![], Compression::default());
+    gzip.write_all(&payload)?;
+    let ciphertext =
+        cbc::Encryptor::<aes::Aes256>::new(&master_key, &iv.into()).encrypt_padded_vec_mut::<Pkcs7>(&gzip.finish()?);
+
+    let mut database = header.clone();
+    database.extend(Sha256::digest(&header));
+    let mut mac = block_hmac(&hmac_key, u64::MAX);
+    mac.update(&header);
+    database.extend(mac.finalize().into_bytes());
+    for (index, block) in ciphertext.chunks(BLOCK_SIZE).chain([&[][..]]).enumerate() {
+        let len = (block.len() as u32).to_le_bytes();
+        let mut mac = block_hmac(&hmac_key, index as u64);
+        mac.update(&(index as u64).to_le_bytes());
+        mac.update(&len);
+        mac.update(block);
+        database.extend(mac.finalize().into_bytes());
+        database.extend(len);
+        database.extend(block);
+    }
+    Ok(database)
+}
+
+/// Accounts of an entry's OTP attributes, none when it has no OTP
+fn entry_accounts(strings: &HashMap<&str, &str>) -> Result<Vec<Account>, Box<dyn Error>> {
+    let title = strings.get("Title").copied().unwrap_or_default();
+    let user_name = strings.get("UserName").copied().unwrap_or_default();
+
+    let mut accounts = match (strings.get("otp"), strings.get("TOTP Seed")) {
+        (Some(otp), _) if otp.starts_with("otpauth://") => totp_token::get_accounts(otp)?,
+        (Some(otp), _) => vec![keeotp(otp)?],
+        (None, Some(seed)) => {
+            // "period;digits", KeePassXC writes "S" for Steam tokens in place of digits
+            let settings = strings.get("TOTP Settings").copied().unwrap_or("30;6");
+            let (period, digits) = settings.split_once(';').unwrap_or((settings, "6"));
+            let (otp_type, digits) = match digits {
+                "S" => (OtpType::Steam, totp_token::STEAM_DIGITS),
+                digits => (
+                    OtpType::Totp,
+                    totp_token::parse_digits(digits).map_err(|e| format!("TOTP Settings `{settings}`: {e}"))?,
+                ),
+            };
+            vec![Account {
+                secret: seed.replace(' ', ""),
+                issuer: String::new(),
+                account: String::new(),
+                sha: Algorithm::SHA1,
+                digits,
+                period: period
+                    .parse()
+                    .map_err(|_| format!("invalid TOTP Settings `{settings}`"))?,
+                otp_type,
+                counter: 0,
+            }]
+        }
+        (None, None) => vec![],
+    };
+    for account in &mut accounts {
+        if account.issuer.is_empty() {
+            account.issuer = title.into();
+        }
+        if account.account.is_empty() {
+            account.account = user_name.into();
+        }
+    }
+    Ok(accounts)
+}
+
+/// KeeOTP's "key=SECRET&size=6&step=30&otpHashMode=SHA256"
+fn keeotp(otp: &str) -> Result<Account, Box<dyn Error>> {
+    let params: HashMap<_, _> = otp.split('&').filter_map(|param| param.split_once('=')).collect();
+    let secret = params.get("key").ok_or("otp attribute has no key")?;
+    Ok(Account {
+        secret: urlencoding::decode(secret)?.into_owned(),
+        issuer: String::new(),
+        account: String::new(),
+        sha: params.get("otpHashMode").unwrap_or(&"SHA1").parse()?,
+        digits: totp_token::parse_digits(params.get("size").unwrap_or(&"6"))?,
+        period: params.get("step").unwrap_or(&"30").parse()?,
+        otp_type: OtpType::Totp,
+        counter: 0,
+    })
+}
+
+/// The KeePass XML of the accounts, protected values are masked with the inner stream
+///
+/// Meta, group and entry elements carry the settings and times KeePass writes itself, all dated `now`
+fn to_xml(accounts: &[&Account], stream: &mut ChaCha20, now: u64) -> String {
+    let mut protect = |value: &str| {
+        let mut value = value.as_bytes().to_vec();
+        stream.apply_keystream(&mut value);
+        general_purpose::STANDARD.encode(value)
+    };
+    let time = general_purpose::STANDARD.encode((now + UNIX_EPOCH_SECONDS).to_le_bytes());
+    let times = |indent: &str| {
+        format!(
+            "{indent}<Times>\n\
+             {indent}\t<CreationTime>{time}</CreationTime>\n\
+             {indent}\t<LastModificationTime>{time}</LastModificationTime>\n\
+             {indent}\t<LastAccessTime>{time}</LastAccessTime>\n\
+             {indent}\t<ExpiryTime>{time}</ExpiryTime>\n\
+             {indent}\t<Expires>False</Expires>\n\
+             {indent}\t<UsageCount>0</UsageCount>\n\
+             {indent}\t<LocationChanged>{time}</LocationChanged>\n\
+             {indent}</Times>\n"
+        )
+    };
+    let mut entries = String::new();
+    for account in accounts {
+        let title = match account.issuer.is_empty() {
+            true => &account.account,
+            false => &account.issuer,
+        };
+        entries += &format!(
+            "\t\t\t<Entry>\n\t\t\t\t<UUID>{}</UUID>\n\
+             {}\
+             \t\t\t\t<String><Key>Title</Key><Value>{}</Value></String>\n\
+             \t\t\t\t<String><Key>UserName</Key><Value>{}</Value></String>\n\
+             \t\t\t\t<String><Key>Password</Key><Value Protected=\"True\">{}</Value></String>\n\
+             \t\t\t\t<String><Key>otp</Key><Value Protected=\"True\">{}</Value></String>\n\
+             \t\t\t</Entry>\n",
+            general_purpose::STANDARD.encode(random::<16>()),
+            times("\t\t\t\t"),
+            escape(title),
+            escape(&account.account),
+            protect(""),
+            protect(&OtpAuthUri::from(*account).to_string()),
+        );
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile>\n\
+         \t<Meta>\n\t\t<Generator>totp-qr</Generator>\n\
+         \t\t<DatabaseName>totp-qr</DatabaseName>\n\t\t<DatabaseNameChanged>{time}</DatabaseNameChanged>\n\
+         \t\t<SettingsChanged>{time}</SettingsChanged>\n\
+         \t\t<MemoryProtection>\n\t\t\t<ProtectTitle>False</ProtectTitle>\n\t\t\t<ProtectUserName>False</ProtectUserName>\n\
+         \t\t\t<ProtectPassword>True</ProtectPassword>\n\t\t\t<ProtectURL>False</ProtectURL>\n\
+         \t\t\t<ProtectNotes>False</ProtectNotes>\n\t\t</MemoryProtection>\n\
+         \t\t<RecycleBinEnabled>False</RecycleBinEnabled>\n\t</Meta>\n\
+         \t<Root>\n\t\t<Group>\n\t\t\t<UUID>{}</UUID>\n\t\t\t<Name>Root</Name>\n{}\
+         \t\t\t<IsExpanded>True</IsExpanded>\n{entries}\t\t</Group>\n\t</Root>\n\
+         </KeePassFile>\n",
+        general_purpose::STANDARD.encode(random::<16>()),
+        times("\t\t\t"),
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// SHA-256 of the password hash followed by the key file hash
+///
+/// A key file with an empty password unlocks databases protected by the key file alone
+fn composite_key(password: &str, key_file: Option<&[u8]>) -> Result<[u8; 32], Box<dyn Error>> {
+    let mut composite = Sha256::new();
+    if !password.is_empty() || key_file.is_none() {
+        composite.update(Sha256::digest(password.as_bytes()));
+    }
+    if let Some(key_file) = key_file {
+        composite.update(key_file_key(key_file)?);
+    }
+    Ok(composite.finalize().into())
+}
+
+/// The 32 byte key of a key file: XML (version 1.0 Base-64, 2.0 hex), 32 raw bytes,
+/// 64 hex digits, or the SHA-256 of any other file
+fn key_file_key(key_file: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if let Ok(text) = std::str::from_utf8(key_file) {
+        if text.contains("<KeyFile>") {
+            let document = roxmltree::Document::parse(text).map_err(|e| format!("invalid KeePass key file: {e}"))?;
+            let node = |name| document.descendants().find(|node| node.has_tag_name(name));
+            let data = node("Data")
+                .and_then(|node| node.text())
+                .ok_or("KeePass key file has no Data")?;
+            return match node("Version").and_then(|node| node.text()) {
+                Some(version) if version.starts_with("2.") => {
+                    Ok(hex::decode(data.split_whitespace().collect::<String>())?)
+                }
+                _ => Ok(general_purpose::STANDARD.decode(data.trim())?),
+            };
+        }
+        if text.len() == 64 && text.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Ok(hex::decode(text)?);
+        }
+    }
+    Ok(match key_file.len() {
+        32 => key_file.to_vec(),
+        _ => Sha256::digest(key_file).to_vec(),
+    })
+}
+
+/// The KDF parameters VariantDictionary, values are kept as their little-endian bytes
+fn variant_dictionary(bytes: &[u8]) -> Result<HashMap<String, Vec<u8>>, Box<dyn Error>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.u16()? >> 8 != 1 {
+        return Err("unsupported KeePass KDF parameters version".into());
+    }
+    let mut dictionary = HashMap::new();
+    while reader.u8()? != 0 {
+        let name_len = reader.u32()? as usize;
+        let name = String::from_utf8(reader.take(name_len)?.to_vec())?;
+        let value_len = reader.u32()? as usize;
+        dictionary.insert(name, reader.take(value_len)?.to_vec());
+    }
+    Ok(dictionary)
+}
+
+/// Run the composite key through the database's AES-KDF or Argon2
+fn transform_key(kdf: &HashMap<String, Vec<u8>>, composite: &[u8; 32]) -> Result<[u8; 32], Box<dyn Error>> {
+    let bytes = |name: &str| kdf.get(name).ok_or(format!("KeePass KDF parameter {name} is missing"));
+    let u32 =
+        |name: &str| -> Result<u32, Box<dyn Error>> { Ok(u32::from_le_bytes(bytes(name)?.as_slice().try_into()?)) };
+    let u64 =
+        |name: &str| -> Result<u64, Box<dyn Error>> { Ok(u64::from_le_bytes(bytes(name)?.as_slice().try_into()?)) };
+
+    let mut key = [0u8; 32];
+    let uuid: [u8; 16] = bytes("$UUID")?.as_slice().try_into()?;
+    match uuid {
+        KDF_AES => {
+            let cipher = aes::Aes256::new_from_slice(bytes("S")?).map_err(|_| "invalid KeePass AES-KDF seed")?;
+            key = *composite;
+            for _ in 0..u64("R")? {
+                for block in key.chunks_exact_mut(16) {
+                    cipher.encrypt_block(block.into());
+                }
+            }
+            key = Sha256::digest(key).into();
+        }
+        KDF_ARGON2D | KDF_ARGON2ID => {
+            let algorithm = match uuid {
+                KDF_ARGON2D => argon2::Algorithm::Argon2d,
+                _ => argon2::Algorithm::Argon2id,
+            };
+            let version = match u32("V")? {
+                0x10 => argon2::Version::V0x10,
+                _ => argon2::Version::V0x13,
+            };
+            let memory_kib = u32::try_from(u64("M")? / 1024)?;
+            let params = argon2::Params::new(memory_kib, u32::try_from(u64("I")?)?, u32("P")?, Some(32))
+                .map_err(|e| format!("invalid KeePass argon2 parameters: {e}"))?;
+            argon2::Argon2::new(algorithm, version, params)
+                .hash_password_into(composite, bytes("S")?, &mut key)
+                .map_err(|e| format!("argon2: {e}"))?;
+        }
+        _ => return Err("KeePass key derivation is not supported, only AES-KDF and Argon2".into()),
+    }
+    Ok(key)
+}
+
+/// HMAC-SHA256 keyed for the block index, the header uses index u64::MAX
+fn block_hmac(hmac_key: &[u8], index: u64) -> Hmac<Sha256> {
+    let key = Sha512::new()
+        .chain_update(index.to_le_bytes())
+        .chain_update(hmac_key)
+        .finalize();
+    <Hmac<Sha256> as KeyInit>::new(&key)
+}
+
+/// ChaCha20 keyed by the SHA-512 of the inner stream key
+fn inner_stream(stream_key: &[u8]) -> ChaCha20 {
+    let hash = Sha512::digest(stream_key);
+    ChaCha20::new(hash[..32].into(), hash[32..44].into())
+}
+
+fn write_field(buffer: &mut Vec<u8>, id: u8, data: &[u8]) {
+    buffer.push(id);
+    buffer.extend((data.len() as u32).to_le_bytes());
+    buffer.extend(data);
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
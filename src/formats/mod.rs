@@ -0,0 +1,113 @@
+use crate::totp_token::{self, Account};
+use crate::vault::KdfParams;
+use std::error::Error;
+pub mod aegis;
+pub mod andotp;
+pub mod authpro;
+pub mod bitwarden;
+pub mod csv;
+pub mod ente;
+pub mod freeotp;
+pub mod kdbx;
+pub mod steam;
+pub mod twofas;
+
+/// Account formats read by --import and written by --export
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    // totp-qr JSON accounts
+    #[default]
+    Json,
+    // Aegis Authenticator backup, plain or encrypted on import, plain on export
+    Aegis,
+    // 2FAS `.2fas` backup, plain or encrypted on import, plain on export
+    #[value(name = "2fas")]
+    TwoFas,
+    // andOTP backup, plain or encrypted, import only
+    #[value(name = "andotp")]
+    AndOtp,
+    // FreeOTP+ JSON export, import only
+    #[value(name = "freeotp")]
+    FreeOtp,
+    // KeePass KDBX 4 database, password and optional key file on import, password on export
+    Kdbx,
+    // Unencrypted Bitwarden JSON export
+    Bitwarden,
+    // Ente Auth export, plain or encrypted, import only
+    Ente,
+    // Authenticator Pro backup, plain JSON or encrypted `.authpro`, import only
+    #[value(name = "authpro")]
+    AuthPro,
+    // Steam Desktop Authenticator `.maFile`, import only
+    #[value(name = "mafile")]
+    MaFile,
+    // CSV with a header row, columns found by name or mapped with --csv-column
+    Csv,
+}
+
+/// What formats need besides the file and its password
+#[derive(Debug, Default)]
+pub struct ImportOptions {
+    /// Key file of KeePass databases
+    pub key_file: Option<Vec<u8>>,
+    /// CSV columns mapped to account fields by header
+    pub csv_columns: Vec<(csv::Field, String)>,
+}
+
+/// Asked for the password of an encrypted backup or database, with the prompt to show
+pub type PasswordFn<'a> = &'a mut dyn FnMut(&str) -> Result<String, Box<dyn Error>>;
+
+/// Read the accounts of a backup or export file
+///
+/// The password is only asked for when the backup turns out to be encrypted,
+/// every account is validated
+pub fn import(
+    format: Format,
+    bytes: &[u8],
+    password: PasswordFn,
+    options: &ImportOptions,
+) -> Result<Vec<Account>, Box<dyn Error>> {
+    let accounts =
+        match format {
+            Format::Json => Ok(serde_json::from_slice(bytes)
+                .map_err(|e| format!("serde: Deserializing JSON into Vec<Account>: {e}"))?),
+            Format::Aegis => aegis::import(bytes, password),
+            Format::TwoFas => twofas::import(bytes, password),
+            Format::AndOtp => andotp::import(bytes, password),
+            Format::FreeOtp => freeotp::import(bytes),
+            Format::Kdbx => kdbx::import(
+                bytes,
+                &password("enter KeePass database password:")?,
+                options.key_file.as_deref(),
+            ),
+            Format::Bitwarden => bitwarden::import(bytes),
+            Format::Ente => ente::import(bytes, password),
+            Format::AuthPro => authpro::import(bytes, password),
+            Format::MaFile => steam::import(bytes),
+            Format::Csv => csv::import(bytes, &options.csv_columns),
+        }?;
+    totp_token::validate(accounts)
+}
+
+/// Write the accounts in the format, text formats end with a newline
+///
+/// The password is only asked for by formats written encrypted
+pub fn export(format: Format, accounts: &[&Account], password: PasswordFn) -> Result<Vec<u8>, Box<dyn Error>> {
+    let text = match format {
+        Format::Json => serde_json::to_string(accounts)?,
+        Format::Aegis => aegis::export(accounts)?,
+        Format::TwoFas => twofas::export(accounts)?,
+        Format::Bitwarden => bitwarden::export(accounts)?,
+        Format::Csv => csv::export(accounts)?,
+        Format::AndOtp => return Err("andOTP backups can only be imported".into()),
+        Format::FreeOtp => return Err("FreeOTP+ exports can only be imported".into()),
+        Format::Ente => return Err("Ente Auth exports can only be imported".into()),
+        Format::AuthPro => return Err("Authenticator Pro backups can only be imported".into()),
+        Format::MaFile => return Err("Steam maFiles can only be imported".into()),
+        Format::Kdbx => {
+            let password = password("enter new KeePass database password:")?;
+            return kdbx::export(accounts, &password, &KdfParams::new());
+        }
+    };
+    Ok((text + "\n").into_bytes())
+}
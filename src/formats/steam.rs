@@ -0,0 +1,35 @@
+use crate::otpauth_uri::OtpType;
+use crate::totp_token::{self, Account, Algorithm};
+use base64::{engine::general_purpose, Engine as _};
+use serde::Deserialize;
+use std::error::Error;
+
+/// Steam Desktop Authenticator `.maFile`, only the TOTP key is read
+#[derive(Debug, Deserialize)]
+struct MaFile {
+    shared_secret: String,
+    #[serde(default)]
+    account_name: String,
+}
+
+/// Read the Steam Guard account of an unencrypted `.maFile`, the shared secret is Base-64
+pub fn import(bytes: &[u8]) -> Result<Vec<Account>, Box<dyn Error>> {
+    if !bytes.trim_ascii_start().starts_with(b"{") {
+        return Err("encrypted maFiles are not supported, remove the encryption in Steam Desktop Authenticator".into());
+    }
+    let ma_file: MaFile = serde_json::from_slice(bytes).map_err(|e| format!("not a Steam maFile: {e}"))?;
+    let secret = general_purpose::STANDARD
+        .decode(ma_file.shared_secret.trim())
+        .map_err(|e| format!("invalid maFile shared_secret: {e}"))?;
+
+    Ok(vec![Account {
+        secret: base32::encode(base32::Alphabet::RFC4648 { padding: false }, &secret),
+        issuer: "Steam".into(),
+        account: ma_file.account_name,
+        sha: Algorithm::SHA1,
+        digits: totp_token::STEAM_DIGITS,
+        period: 30,
+        otp_type: OtpType::Steam,
+        counter: 0,
+    }])
+}
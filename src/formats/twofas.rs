@@ -0,0 +1,167 @@
+use crate::formats::PasswordFn;
+use crate::otpauth_uri::OtpType;
+use crate::totp_token::Account;
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 2FAS derives the backup key with PBKDF2-HMAC-SHA256
+const ITERATIONS: u32 = 10000;
+const SCHEMA_VERSION: u32 = 4;
+
+/// 2FAS `.2fas` backup, encrypted backups leave `services` empty and fill `servicesEncrypted`
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Backup {
+    services: Vec<Service>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    services_encrypted: Option<String>,
+    #[serde(default)]
+    groups: Vec<serde_json::Value>,
+    #[serde(default)]
+    updated_at: u64,
+    #[serde(default)]
+    schema_version: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Service {
+    name: String,
+    secret: String,
+    #[serde(default)]
+    updated_at: u64,
+    otp: Otp,
+    #[serde(default)]
+    order: Order,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Otp {
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    account: Option<String>,
+    #[serde(default)]
+    issuer: Option<String>,
+    digits: Option<u32>,
+    period: Option<u64>,
+    algorithm: Option<String>,
+    counter: Option<u64>,
+    token_type: Option<String>,
+    #[serde(default)]
+    source: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Order {
+    position: usize,
+}
+
+/// Read the accounts of a plain or encrypted 2FAS backup
+pub fn import(bytes: &[u8], password: PasswordFn) -> Result<Vec<Account>, Box<dyn Error>> {
+    let backup: Backup = serde_json::from_slice(bytes).map_err(|e| format!("not a 2FAS backup: {e}"))?;
+    let services = match backup.services_encrypted {
+        Some(encrypted) if backup.services.is_empty() => {
+            let plaintext = decrypt(&encrypted, &password("enter 2FAS backup password:")?)?;
+            serde_json::from_slice(&plaintext).map_err(|e| format!("invalid 2FAS services: {e}"))?
+        }
+        _ => backup.services,
+    };
+
+    let mut accounts = vec![];
+    for service in services {
+        let otp = service.otp;
+        let otp_type = match otp.token_type.as_deref().unwrap_or("TOTP") {
+            "TOTP" => OtpType::Totp,
+            "HOTP" => OtpType::Hotp,
+            "STEAM" => OtpType::Steam,
+            other => {
+                eprintln!("Skipping 2FAS service {}, unsupported type `{other}`", service.name);
+                continue;
+            }
+        };
+        accounts.push(Account {
+            secret: service.secret,
+            issuer: otp.issuer.filter(|issuer| !issuer.is_empty()).unwrap_or(service.name),
+            account: otp.account.unwrap_or_default(),
+            sha: otp.algorithm.as_deref().unwrap_or("SHA1").parse()?,
+            digits: otp.digits.unwrap_or(6),
+            period: otp.period.unwrap_or(30),
+            otp_type,
+            counter: otp.counter.unwrap_or_default(),
+        });
+    }
+    Ok(accounts)
+}
+
+/// A plain 2FAS backup of the accounts
+pub fn export(accounts: &[&Account]) -> Result<String, Box<dyn Error>> {
+    let updated_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    let services = accounts
+        .iter()
+        .enumerate()
+        .map(|(position, account)| Service {
+            name: account.issuer.clone(),
+            secret: account.secret.clone(),
+            updated_at,
+            otp: Otp {
+                label: Some(match account.issuer.is_empty() {
+                    true => account.account.clone(),
+                    false => format!("{}:{}", account.issuer, account.account),
+                }),
+                account: Some(account.account.clone()),
+                issuer: Some(account.issuer.clone()),
+                digits: Some(account.digits),
+                period: Some(account.period),
+                algorithm: Some(account.sha.to_string()),
+                counter: Some(account.counter),
+                token_type: Some(
+                    match account.otp_type {
+                        OtpType::Totp => "TOTP",
+                        OtpType::Hotp => "HOTP",
+                        OtpType::Steam => "STEAM",
+                    }
+                    .into(),
+                ),
+                source: Some("Link".into()),
+            },
+            order: Order { position },
+        })
+        .collect();
+
+    let backup = Backup {
+        services,
+        services_encrypted: None,
+        groups: vec![],
+        updated_at,
+        schema_version: SCHEMA_VERSION,
+    };
+    Ok(serde_json::to_string_pretty(&backup)?)
+}
+
+/// Decrypt `servicesEncrypted`, "Base-64 ciphertext and tag:Base-64 salt:Base-64 IV"
+fn decrypt(encrypted: &str, password: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let parts = encrypted
+        .split(':')
+        .map(|part| general_purpose::STANDARD.decode(part))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid 2FAS servicesEncrypted: {e}"))?;
+    let [ciphertext, salt, iv] = parts.as_slice() else {
+        return Err("invalid 2FAS servicesEncrypted, expected ciphertext:salt:iv".into());
+    };
+    if iv.len() != 12 {
+        return Err("invalid 2FAS IV".into());
+    }
+
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, ITERATIONS, &mut key);
+    Aes256Gcm::new(&key.into())
+        .decrypt(Nonce::from_slice(iv), ciphertext.as_slice())
+        .map_err(|_| "2FAS backup decryption failed, wrong password?".into())
+}
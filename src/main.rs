@@ -2,50 +2,334 @@
 #![doc = include_str!("../README.md")]
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use file_format::{FileFormat, Kind};
-use image::io::Reader as ImageReader;
-use rqrr::PreparedImage;
 use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
-use std::io::{self, Cursor, Read};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // derivative work adopted from:
 // https://alexbakker.me/post/parsing-google-auth-export-qr-code.html
 // https://github.com/zhangyuan/google-authenticator-extractor/tree/master/src/protos
 // https://docs.rs/totp-rs/latest/totp_rs
+mod formats;
+mod openssl_enc;
 mod otpauth_migration;
+mod otpauth_uri;
+mod password;
+mod password_store;
+mod qr_decode;
+mod qr_encode;
+mod shell_function;
 mod totp_token;
+mod vault;
+use crate::otpauth_uri::OtpType;
 use crate::totp_token::Account;
 
+#[derive(clap::Args, Debug)]
+struct InputArgs {
+    /// "otpauth-migration://offline?data=..." or "otpauth://totp/...?secret=SECRET"
+    #[arg(short, long)]
+    auth: Option<String>,
+
+    /// Import accounts, JSON unless another FORMAT is given
+    #[arg(short, long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "json")]
+    import: Option<formats::Format>,
+
+    /// Read the password of encrypted input from file descriptor FD instead of prompting
+    #[arg(long, value_name = "FD")]
+    password_fd: Option<i32>,
+
+    /// KeePass key file of --import=kdbx databases
+    #[arg(long, value_name = "FILE")]
+    key_file: Option<PathBuf>,
+
+    /// Map a CSV column to an account field by header, FIELD is issuer|account|secret|algorithm|digits|period|otpauth
+    #[arg(long, value_name = "FIELD=HEADER", value_parser = formats::csv::parse_column)]
+    csv_column: Vec<(formats::csv::Field, String)>,
+
+    /// image-files|stdin, filename of "-" implies stdin
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct DisplayArgs {
+    /// Verbose output
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Export account information as JSON unless another FORMAT is given
+    #[arg(short, long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "json")]
+    export: Option<formats::Format>,
+
+    /// Read the password of an encrypted export from file descriptor FD instead of prompting twice
+    #[arg(long, value_name = "FD")]
+    export_password_fd: Option<i32>,
+
+    /// Output account URI's
+    #[arg(short, long)]
+    uri: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Keep accounts in a password encrypted vault file (Argon2id, XChaCha20-Poly1305)
+    Vault {
+        /// Vault file, "-" reads a vault from stdin [default: ~/.totp-qr.vault]
+        #[arg(long, env = "TOTP_QR_VAULT")]
+        file: Option<PathBuf>,
+
+        /// Read the vault password from file descriptor FD instead of prompting
+        #[arg(long, value_name = "FD")]
+        password_fd: Option<i32>,
+
+        #[command(subcommand)]
+        action: VaultAction,
+    },
+
+    /// Print a shell function holding the accounts in an encrypted vault, calling it displays their tokens
+    ShellFunction {
+        /// Function name
+        #[arg(long, default_value = "totp")]
+        name: String,
+
+        /// Shell syntax of the function
+        #[arg(long, value_enum, default_value_t = shell_function::Shell::Bash)]
+        shell: shell_function::Shell,
+
+        /// Order of the displayed tokens
+        #[arg(long, value_enum, default_value_t = shell_function::SortOrder::Issuer)]
+        sort: shell_function::SortOrder,
+
+        /// Read the password of the embedded vault from file descriptor FD instead of prompting
+        #[arg(long, value_name = "FD")]
+        vault_password_fd: Option<i32>,
+
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    /// Use pass-otp entries of a pass password store, decrypted and encrypted with gpg
+    Pass {
+        /// Password store directory [default: ~/.password-store]
+        #[arg(long, env = "PASSWORD_STORE_DIR")]
+        store: Option<PathBuf>,
+
+        #[command(subcommand)]
+        action: PassAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PassAction {
+    /// Display tokens of the otpauth lines in entries, optionally only for entry paths containing PATTERN
+    Code {
+        /// Only entry paths containing PATTERN
+        pattern: Option<String>,
+
+        #[command(flatten)]
+        display: DisplayArgs,
+    },
+
+    /// Insert accounts decoded from images, otpauth URI's or JSON as PREFIX/ISSUER/ACCOUNT entries
+    Insert {
+        /// Entry path prefix, e.g. "otp"
+        #[arg(long, default_value = "")]
+        prefix: String,
+
+        /// Replace existing entries
+        #[arg(long)]
+        force: bool,
+
+        #[command(flatten)]
+        input: InputArgs,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum VaultAction {
+    /// Create an empty vault
+    Init,
+
+    /// Add accounts decoded from images, otpauth URI's or JSON
+    Add(InputArgs),
+
+    /// List the issuer and account name of each account
+    List,
+
+    /// Display tokens, optionally only for issuers containing ISSUER
+    Code {
+        /// Only issuers containing ISSUER
+        issuer: Option<String>,
+
+        #[command(flatten)]
+        display: DisplayArgs,
+    },
+}
+
+fn default_vault() -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    PathBuf::from(home.unwrap_or_default()).join(".totp-qr.vault")
+}
+
+// Run a vault action, accounts are decrypted in memory and never leave the process in the clear
+fn run_vault(file: &Path, password_fd: Option<i32>, action: VaultAction) -> Result<(), Box<dyn Error>> {
+    let prompt = "enter totp-qr vault password:";
+    match action {
+        VaultAction::Init => {
+            if file.exists() {
+                return Err(format!("vault `{}` already exists", file.display()).into());
+            }
+            let password = password::read_new(prompt, password_fd)?;
+            vault::write(file, &[], &password)?;
+            eprintln!("Created {}", file.display());
+        }
+        VaultAction::Add(input) => {
+            let mut accinfo = HashMap::new();
+            read_inputs(input, &mut accinfo, &mut HashMap::new())?;
+
+            let password = password::read(prompt, password_fd)?;
+            let mut accounts = vault::read(file, &password)?;
+            let count = accounts.len();
+            add_unique(&mut accounts, accinfo.into_values().flatten());
+            vault::write(file, &accounts, &password)?;
+            eprintln!("Added {} account(s) to {}", accounts.len() - count, file.display());
+        }
+        VaultAction::List => {
+            let password = password::read(prompt, password_fd)?;
+            for account in vault::read(file, &password)? {
+                println!("{}", account.name());
+            }
+        }
+        VaultAction::Code { issuer, display } => {
+            let password = password::read(prompt, password_fd)?;
+            let pattern = issuer.unwrap_or_default().to_lowercase();
+            let accinfo: HashMap<_, _> = vault::read(file, &password)?
+                .into_iter()
+                .filter(|account| account.issuer.to_lowercase().contains(&pattern))
+                .map(|account| (account.uri(), vec![account]))
+                .collect();
+            display_accounts(&accinfo, &HashMap::new(), &display)?;
+        }
+    }
+    Ok(())
+}
+
+// Run a pass action, gpg asks for the key passphrase through its agent
+fn run_pass(store: &Path, action: PassAction) -> Result<(), Box<dyn Error>> {
+    match action {
+        PassAction::Code { pattern, display } => {
+            let pattern = pattern.unwrap_or_default().to_lowercase();
+            let mut accinfo: HashMap<String, Vec<Account>> = HashMap::new();
+            let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+            for entry in password_store::entries(store)? {
+                if !entry.to_lowercase().contains(&pattern) {
+                    continue;
+                }
+                let accounts = match password_store::read_accounts(store, &entry, None) {
+                    Ok(accounts) => accounts,
+                    Err(e) => {
+                        eprintln!("Skipping pass {entry}: {e}");
+                        continue;
+                    }
+                };
+                for account in accounts {
+                    sources.entry(account.uri()).or_default().push(format!("pass {entry}"));
+                    accinfo.insert(account.uri(), vec![account]);
+                }
+            }
+            if accinfo.is_empty() {
+                return Err(format!("no pass-otp entries match `{pattern}` in `{}`", store.display()).into());
+            }
+            display_accounts(&accinfo, &sources, &display)?;
+        }
+        PassAction::Insert { prefix, force, input } => {
+            let mut accinfo = HashMap::new();
+            read_inputs(input, &mut accinfo, &mut HashMap::new())?;
+            let mut accounts = vec![];
+            add_unique(&mut accounts, accinfo.into_values().flatten());
+            for account in accounts {
+                let entry = password_store::entry_name(&prefix, &account);
+                password_store::insert(store, &entry, &account, force, None)?;
+                eprintln!("Inserted {entry}");
+            }
+        }
+    }
+    Ok(())
+}
+
+// Append the accounts not already present
+fn add_unique(accounts: &mut Vec<Account>, new: impl IntoIterator<Item = Account>) {
+    for account in new {
+        if !accounts.contains(&account) {
+            accounts.push(account);
+        }
+    }
+}
+
+// Encrypt the accounts into a vault embedded in a shell function, replaces scripts/mk-totp-func.sh
+fn run_shell_function(
+    name: &str,
+    shell: shell_function::Shell,
+    sort: shell_function::SortOrder,
+    password_fd: Option<i32>,
+    input: InputArgs,
+) -> Result<(), Box<dyn Error>> {
+    let mut accinfo = HashMap::new();
+    read_inputs(input, &mut accinfo, &mut HashMap::new())?;
+    let mut accounts = vec![];
+    add_unique(&mut accounts, accinfo.into_values().flatten());
+    if accounts.is_empty() {
+        return Err("no accounts to put in the shell function".into());
+    }
+
+    let password = password::read_new("enter totp-qr vault password:", password_fd)?;
+    let vault = vault::seal(&accounts, &password, vault::KdfParams::new())?;
+    print!("{}", shell_function::render(name, shell, sort, &vault)?);
+    Ok(())
+}
+
 // Display the TOTP token and Account detail
 fn display_accounts(
     accinfo: &HashMap<String, Vec<Account>>,
-    uri: bool,
-    export: bool,
-    verbose: bool,
+    sources: &HashMap<String, Vec<String>>,
+    display: &DisplayArgs,
 ) -> Result<(), Box<dyn Error>> {
-    if uri {
-        for otpauth in accinfo.keys() {
-            println!("{otpauth}");
+    let verbose = display.verbose;
+    if display.uri {
+        for (otpauth, accounts) in accinfo {
+            match otpauth.starts_with("otpauth") {
+                true => println!("{otpauth}"),
+                false => accounts.iter().for_each(|account| println!("{}", account.uri())),
+            }
         }
-    } else if export {
+    } else if let Some(format) = display.export {
         let acc: Vec<_> = accinfo.values().flatten().collect();
-        println!("{}", serde_json::to_string(&acc)?);
+        let mut read_password = |prompt: &str| password::read_new(prompt, display.export_password_fd);
+        io::stdout().write_all(&formats::export(format, &acc, &mut read_password)?)?;
     } else {
         let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
         for (otpauth, accounts) in accinfo {
             if verbose && otpauth.starts_with("otpauth") {
                 println!("otpauth = {otpauth}");
             }
+            if verbose {
+                for source in sources.get(otpauth).into_iter().flatten() {
+                    println!("source = {source}");
+                }
+            }
             for account in accounts {
-                let token = totp_token::time_token(time, account)?;
+                let token = match account.otp_type {
+                    OtpType::Totp | OtpType::Steam => totp_token::time_token(time, account)?,
+                    OtpType::Hotp => totp_token::hotp_token(account.counter, account)?,
+                };
                 if verbose {
                     println!("{token}, {account:?}");
                 } else {
-                    println!("{token}, {}", account.issuer);
+                    println!("{token}, {}", account.name());
                 }
             }
             if verbose {
@@ -56,45 +340,78 @@ fn display_accounts(
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    #[derive(Parser, Debug)]
-    #[clap(author, version, about)]
-    struct Args {
-        /// "otpauth-migration://offline?data=..." or "otpauth://totp/...?secret=SECRET"
-        #[arg(short, long)]
-        auth: Option<String>,
-
-        /// Verbose output
-        #[arg(short, long)]
-        verbose: bool,
-
-        /// Export account information as JSON
-        #[arg(short, long)]
-        export: bool,
-
-        /// Import JSON accounts
-        #[arg(short, long)]
-        import: bool,
+// Warn about Google Authenticator exports with missing or repeated QR codes
+fn check_migration_batches(
+    accinfo: &HashMap<String, Vec<Account>>,
+    require_complete: bool,
+) -> Result<(), Box<dyn Error>> {
+    let uris = accinfo
+        .keys()
+        .filter(|otpauth| otpauth.contains("otpauth-migration://offline"))
+        .map(String::as_str);
 
-        /// Output account URI's
-        #[arg(short, long)]
-        uri: bool,
+    let mut incomplete = 0;
+    for batch in otpauth_migration::group_batches(uris)? {
+        for index in batch.missing() {
+            eprintln!(
+                "Warning: Google Authenticator export (batch_id {}) is missing QR code {} of {}",
+                batch.batch_id,
+                index + 1,
+                batch.batch_size
+            );
+        }
+        for index in &batch.repeated {
+            eprintln!(
+                "Warning: Google Authenticator export (batch_id {}) has different QR codes numbered {} of {}",
+                batch.batch_id,
+                index + 1,
+                batch.batch_size
+            );
+        }
+        if !batch.is_complete() {
+            incomplete += 1;
+        }
+    }
 
-        /// image-files|stdin, filename of "-" implies stdin
-        files: Vec<std::path::PathBuf>,
+    match incomplete > 0 && require_complete {
+        true => Err(format!("{incomplete} incomplete Google Authenticator export(s)").into()),
+        false => Ok(()),
     }
-    let args = Args::parse();
+}
 
-    // ===============================================================
+// Decode accounts from the --auth URI, or from images, otpauth text or JSON in files|stdin
+//
+// Input encrypted with `openssl aes-256-cbc -e -pbkdf2 [-a]` is decrypted first, the
+// password is asked for once and used for every encrypted input and backup
+fn read_inputs(
+    input: InputArgs,
+    accinfo: &mut HashMap<String, Vec<Account>>,
+    sources: &mut HashMap<String, Vec<String>>,
+) -> Result<(), Box<dyn Error>> {
+    let files = match input.files.is_empty() {
+        true => vec![PathBuf::from("-")],
+        false => input.files,
+    };
 
-    let mut accinfo: HashMap<String, Vec<Account>> = HashMap::new();
+    let options = formats::ImportOptions {
+        key_file: match &input.key_file {
+            Some(path) => {
+                Some(fs::read(path).with_context(|| format!("could not read key file `{}`", path.display()))?)
+            }
+            None => None,
+        },
+        csv_columns: input.csv_column,
+    };
 
-    let files = match args.files.is_empty() {
-        true => vec![std::path::PathBuf::from("-")],
-        false => args.files,
+    let mut password: Option<String> = None;
+    let mut read_password = |prompt: &str| -> Result<String, Box<dyn Error>> {
+        if password.is_none() {
+            password = Some(password::read(prompt, input.password_fd)?);
+        }
+        Ok(password.clone().unwrap_or_default())
     };
 
-    if let Some(otpauth) = args.auth {
+    if let Some(otpauth) = input.auth {
         accinfo.insert(otpauth.clone(), totp_token::get_accounts(&otpauth)?);
     } else {
         for file in files {
@@ -116,11 +433,15 @@ fn main() -> Result<(), Box<dyn Error>> {
                 }
             };
 
-            if args.import {
-                let json = std::str::from_utf8(&bytes)?;
-                let imported_accounts: Vec<Account> =
-                    serde_json::from_str(json).with_context(|| "serde: Deserializing JSON into Vec<Account>")?;
-                accinfo.insert(json.into(), imported_accounts);
+            if openssl_enc::is_salted(&bytes) {
+                let password = read_password("enter AES-256-CBC decryption password:")?;
+                bytes = openssl_enc::decrypt(&bytes, &password).map_err(|e| format!("{input_name}: {e}"))?;
+            }
+
+            if let Some(format) = input.import {
+                let imported_accounts = formats::import(format, &bytes, &mut read_password, &options)
+                    .map_err(|e| format!("{input_name}: {e}"))?;
+                accinfo.insert(String::from_utf8_lossy(&bytes).into(), imported_accounts);
             } else {
                 // Inspect the bytes to classifying as Image or Text
                 let format = FileFormat::from_bytes(&bytes);
@@ -129,35 +450,156 @@ fn main() -> Result<(), Box<dyn Error>> {
                         accinfo.insert(otpauth.into(), totp_token::get_accounts(otpauth)?);
                     }
                 } else {
-                    // Detect the image format and decode the bytes into a Luma image
-                    let img = ImageReader::new(Cursor::new(bytes))
-                        .with_guessed_format()?
-                        .decode()?
-                        .to_luma8();
-
-                    // Prepare for detection
-                    let mut img = PreparedImage::prepare(img);
-
-                    // Search for grids, without decoding
-                    match img.detect_grids() {
-                        grids if grids.len() == 1 => {
-                            // Decode the grid and obtain the otpauth string
-                            // e.g. otpauth://totp/Site:User?Secret=Base-32&period=30&digits=6&issuer=SiteName
-                            // e.g. otpauth-migration://offline?data=Base-64
-                            let (_meta, otpauth) = grids[0].decode()?;
-                            accinfo.insert(otpauth.clone(), totp_token::get_accounts(&otpauth)?);
+                    let decoded = qr_decode::decode_grids(bytes)?;
+                    if decoded.grids.is_empty() {
+                        eprintln!("Skipping {input_name}, no QR code found");
+                    }
+                    for (index, grid) in decoded.grids.into_iter().enumerate() {
+                        let source = format!("{input_name} [grid {index}, {}]", decoded.strategy);
+                        match grid {
+                            Ok(otpauth) if otpauth.starts_with("otpauth") => {
+                                let accounts =
+                                    totp_token::get_accounts(&otpauth).map_err(|e| format!("{source}: {e}"))?;
+                                accinfo.insert(otpauth.clone(), accounts);
+                                sources.entry(otpauth).or_default().push(source);
+                            }
+                            Ok(payload) => eprintln!("Skipping {source}, not an otpauth URI: {payload}"),
+                            Err(e) => eprintln!("Skipping {source}, {e}"),
                         }
-                        grids => eprintln!(
-                            "Skipping {input_name}, expected 1 image grid, found {} grids",
-                            grids.len()
-                        ),
                     }
                 }
             }
         }
     }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    #[derive(Parser, Debug)]
+    #[clap(author, version, about, args_conflicts_with_subcommands = true)]
+    struct Args {
+        #[command(subcommand)]
+        command: Option<Command>,
+
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        display: DisplayArgs,
+
+        /// Output accounts as otpauth-migration URI's (Google Authenticator export)
+        #[arg(short, long)]
+        migration: bool,
+
+        /// Write a QR image (PNG and SVG) per account, or per migration batch, into DIR
+        #[arg(short, long, value_name = "DIR")]
+        qr: Option<PathBuf>,
+
+        /// Draw the QR code of accounts whose issuer contains ISSUER in the terminal
+        #[arg(short, long, value_name = "ISSUER")]
+        show: Option<String>,
+
+        /// Draw terminal QR codes with plain ASCII instead of Unicode half blocks
+        #[arg(long, requires = "show")]
+        ascii: bool,
+
+        /// Fail when a multi-QR Google Authenticator export is missing QR codes
+        #[arg(long)]
+        require_complete: bool,
+    }
+    let args = Args::parse();
+
+    match args.command {
+        Some(Command::Vault {
+            file,
+            password_fd,
+            action,
+        }) => return run_vault(&file.unwrap_or_else(default_vault), password_fd, action),
+        Some(Command::ShellFunction {
+            name,
+            shell,
+            sort,
+            vault_password_fd,
+            input,
+        }) => return run_shell_function(&name, shell, sort, vault_password_fd, input),
+        Some(Command::Pass { store, action }) => {
+            return run_pass(&store.unwrap_or_else(password_store::default_store), action)
+        }
+        None => {}
+    }
+
+    // ===============================================================
+
+    let mut accinfo: HashMap<String, Vec<Account>> = HashMap::new();
+
+    // QR image sources of each otpauth string, "file [grid N, preprocessing strategy]"
+    let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+
+    read_inputs(args.input, &mut accinfo, &mut sources)?;
+
+    check_migration_batches(&accinfo, args.require_complete)?;
+
+    // Google Authenticator uses a random batch_id to tie the QR codes of one export together
+    let migration_uris = match args.migration {
+        true => {
+            let accounts: Vec<_> = accinfo.values().flatten().collect();
+            let batch_id = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos() as i32;
+            otpauth_migration::to_uris(&accounts, batch_id)?
+        }
+        false => vec![],
+    };
+
+    if let Some(dir) = args.qr {
+        let paths = match args.migration {
+            true => {
+                let images: Vec<_> = migration_uris
+                    .iter()
+                    .enumerate()
+                    .map(|(i, uri)| {
+                        (
+                            format!("otpauth-migration-{}-of-{}", i + 1, migration_uris.len()),
+                            uri.into(),
+                        )
+                    })
+                    .collect();
+                qr_encode::write_uri_images(&dir, &images)?
+            }
+            false => {
+                let accounts: Vec<_> = accinfo.values().flatten().collect();
+                qr_encode::write_images(&dir, &accounts)?
+            }
+        };
+        for path in paths {
+            eprintln!("Wrote {}", path.display());
+        }
+        return Ok(());
+    }
+
+    if args.migration {
+        for uri in migration_uris {
+            println!("{uri}");
+        }
+        return Ok(());
+    }
+
+    if let Some(issuer) = args.show {
+        let pattern = issuer.to_lowercase();
+        let accounts: Vec<_> = accinfo
+            .values()
+            .flatten()
+            .filter(|account| account.issuer.to_lowercase().contains(&pattern))
+            .collect();
+        if accounts.is_empty() {
+            return Err(format!("no account issuer matches `{issuer}`").into());
+        }
+        for account in accounts {
+            println!("{}, {}", account.issuer, account.account);
+            println!("{}", qr_encode::to_terminal(account, args.ascii)?);
+        }
+        return Ok(());
+    }
 
-    display_accounts(&accinfo, args.uri, args.export, args.verbose)
+    display_accounts(&accinfo, &sources, &args.display)
 }
 
 // ===============================================================
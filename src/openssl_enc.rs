@@ -0,0 +1,47 @@
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use base64::{engine::general_purpose, Engine as _};
+use sha2::Sha256;
+use std::error::Error;
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+// `openssl enc` prefixes the 8 byte salt with this magic, "U2FsdGVkX1" once Base-64 encoded
+const MAGIC: &[u8] = b"Salted__";
+const MAGIC_BASE64: &[u8] = b"U2FsdGVkX1";
+
+// OpenSSL's -pbkdf2 default iteration count
+const ITERATIONS: u32 = 10000;
+
+/// Does the data look like `openssl enc` output, binary or Base-64
+pub fn is_salted(bytes: &[u8]) -> bool {
+    let bytes = bytes.trim_ascii_start();
+    bytes.starts_with(MAGIC) || bytes.starts_with(MAGIC_BASE64)
+}
+
+/// Decrypt the output of `openssl aes-256-cbc -e -pbkdf2 [-a|-A]`
+///
+/// The key and IV are derived from the password and salt with PBKDF2-HMAC-SHA256
+/// at 10000 iterations, the data may be binary or Base-64 on one or many lines
+pub fn decrypt(bytes: &[u8], password: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let bytes = match bytes.trim_ascii_start().starts_with(MAGIC) {
+        true => bytes.trim_ascii_start().to_vec(),
+        false => {
+            let text: Vec<u8> = bytes.iter().filter(|b| !b.is_ascii_whitespace()).copied().collect();
+            general_purpose::STANDARD
+                .decode(text)
+                .map_err(|e| format!("invalid Base-64 OpenSSL data: {e}"))?
+        }
+    };
+    if bytes.len() < 16 || !bytes.starts_with(MAGIC) {
+        return Err("not OpenSSL encrypted data, missing the `Salted__` header".into());
+    }
+    let (salt, ciphertext) = bytes[8..].split_at(8);
+
+    let mut key_iv = [0u8; 48];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, ITERATIONS, &mut key_iv);
+    let (key, iv) = key_iv.split_at(32);
+
+    Aes256CbcDec::new(key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| "AES-256-CBC decryption failed, wrong password?".into())
+}
@@ -1,34 +1,199 @@
-use crate::totp_token::Account;
+use crate::otpauth_uri::{split_label, OtpType};
+use crate::totp_token::{Account, Algorithm};
 use crate::Error;
 use base64::{engine::general_purpose, Engine as _};
 use protobuf::Message;
+use std::collections::BTreeMap;
 mod proto;
+use proto::google_auth::{
+    MigrationPayload, MigrationPayload_Algorithm, MigrationPayload_DigitCount, MigrationPayload_OtpParameters,
+    MigrationPayload_OtpType,
+};
+
+// Google Authenticator puts at most 10 accounts in each exported QR code
+pub const BATCH_LIMIT: usize = 10;
+
+/// The payloads of one multi-QR Google Authenticator export, grouped by batch_id
+#[derive(Debug, Default)]
+pub struct MigrationBatch {
+    pub batch_id: i32,
+    pub batch_size: i32,
+    /// Accounts by batch_index
+    pub batches: BTreeMap<i32, Vec<Account>>,
+    /// Batch indices seen more than once with different accounts
+    pub repeated: Vec<i32>,
+}
+
+impl MigrationBatch {
+    /// Batch indices of the export that were not seen
+    pub fn missing(&self) -> Vec<i32> {
+        (0..self.batch_size).filter(|i| !self.batches.contains_key(i)).collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing().is_empty() && self.repeated.is_empty()
+    }
+}
+
+/// Group Google Authenticator migration QR code strings by batch_id
+///
+/// The same payload scanned twice is counted once, a batch_index holding different
+/// accounts in two payloads is reported in `repeated`
+pub fn group_batches<'a>(uris: impl IntoIterator<Item = &'a str>) -> Result<Vec<MigrationBatch>, Box<dyn Error>> {
+    let mut groups: BTreeMap<i32, MigrationBatch> = BTreeMap::new();
+    for otpauth in uris {
+        let migration_payload = decode(otpauth)?;
+        let accounts = to_accounts(&migration_payload)?;
+        let group = groups
+            .entry(migration_payload.batch_id)
+            .or_insert_with(|| MigrationBatch {
+                batch_id: migration_payload.batch_id,
+                ..Default::default()
+            });
+        group.batch_size = group.batch_size.max(migration_payload.batch_size);
+        match group.batches.get(&migration_payload.batch_index) {
+            Some(seen) if *seen != accounts => group.repeated.push(migration_payload.batch_index),
+            Some(_) => {}
+            None => {
+                group.batches.insert(migration_payload.batch_index, accounts);
+            }
+        }
+    }
+    Ok(groups.into_values().collect())
+}
 
 /// Convert a Google Authenticator migration QR code string to a list of accounts
 pub fn get_accounts(otpauth: &str) -> Result<Vec<Account>, Box<dyn Error>> {
+    to_accounts(&decode(otpauth)?)
+}
+
+/// Decode the Base-64 protobuf "data=" parameter of a migration QR code string
+fn decode(otpauth: &str) -> Result<MigrationPayload, Box<dyn Error>> {
     let data = match otpauth.split("data=").nth(1) {
         Some(data) => urlencoding::decode(data)?.to_string(),
         _ => return Err("No data found in URI".into()),
     };
     let decoded_data = &general_purpose::STANDARD.decode(data)?;
-    let migration_payload = proto::google_auth::MigrationPayload::parse_from_bytes(decoded_data)?;
+    Ok(MigrationPayload::parse_from_bytes(decoded_data)?)
+}
+
+fn to_accounts(migration_payload: &MigrationPayload) -> Result<Vec<Account>, Box<dyn Error>> {
     let alphabet = base32::Alphabet::RFC4648 { padding: false };
 
-    Ok(migration_payload
+    migration_payload
         .get_otp_parameters()
         .iter()
-        .map(|otp| Account {
-            secret: base32::encode(alphabet, &otp.secret),
-            issuer: match otp.issuer.is_empty() {
-                true => otp.name.to_string(),
-                false => otp.issuer.to_string(),
-            },
-            sha: "SHA1".into(),
-            digits: match otp.digits {
-                2 => 8,
-                _ => 6,
-            },
-            period: 30,
+        .map(|otp| {
+            // The name may carry an "issuer:" prefix like an otpauth label
+            let (label_issuer, account) = split_label(&otp.name);
+
+            // Enum values unknown to the proto definitions are kept aside by the parser,
+            // refuse them rather than generating tokens with the wrong parameters
+            for (field, name) in [(4, "algorithm"), (5, "digits")] {
+                if let Some(values) = otp.get_unknown_fields().get(field) {
+                    return Err(format!("{}: unsupported {name} {:?}", otp.name, values.varint).into());
+                }
+            }
+
+            Ok(Account {
+                secret: base32::encode(alphabet, &otp.secret),
+                issuer: match otp.issuer.is_empty() {
+                    true => label_issuer.unwrap_or_default(),
+                    false => otp.issuer.to_string(),
+                },
+                account,
+                sha: match otp.algorithm {
+                    MigrationPayload_Algorithm::ALGO_SHA256 => Algorithm::SHA256,
+                    MigrationPayload_Algorithm::ALGO_SHA512 => Algorithm::SHA512,
+                    MigrationPayload_Algorithm::ALGO_MD5 => Algorithm::MD5,
+                    _ => Algorithm::SHA1,
+                },
+                digits: match otp.digits {
+                    MigrationPayload_DigitCount::DIGIT_COUNT_EIGHT => 8,
+                    _ => 6,
+                },
+                period: 30,
+                otp_type: match otp.field_type {
+                    MigrationPayload_OtpType::OTP_HOTP => OtpType::Hotp,
+                    _ => OtpType::Totp,
+                },
+                counter: otp.counter as u64,
+            })
         })
-        .collect())
+        .collect()
+}
+
+/// Convert a list of accounts into Google Authenticator migration QR code strings
+///
+/// Accounts are split into batches of at most `BATCH_LIMIT`, the batches share `batch_id`
+pub fn to_uris(accounts: &[&Account], batch_id: i32) -> Result<Vec<String>, Box<dyn Error>> {
+    let otp_parameters = accounts
+        .iter()
+        .map(|account| to_otp_parameters(account))
+        .collect::<Result<Vec<_>, _>>()?;
+    let batches: Vec<_> = otp_parameters.chunks(BATCH_LIMIT).collect();
+
+    let mut uris = vec![];
+    for (batch_index, batch) in batches.iter().enumerate() {
+        let mut migration_payload = MigrationPayload::new();
+        migration_payload.set_otp_parameters(batch.to_vec().into());
+        migration_payload.set_version(1);
+        migration_payload.set_batch_size(batches.len() as i32);
+        migration_payload.set_batch_index(batch_index as i32);
+        migration_payload.set_batch_id(batch_id);
+
+        let data = general_purpose::STANDARD.encode(migration_payload.write_to_bytes()?);
+        uris.push(format!(
+            "otpauth-migration://offline?data={}",
+            urlencoding::encode(&data)
+        ));
+    }
+    Ok(uris)
+}
+
+/// Convert an account into migration parameters, failing on what Authenticator cannot represent
+fn to_otp_parameters(account: &Account) -> Result<MigrationPayload_OtpParameters, Box<dyn Error>> {
+    let name = account.name();
+
+    let mut otp = MigrationPayload_OtpParameters::new();
+    match account.otp_type {
+        OtpType::Totp if account.period != 30 => {
+            return Err(format!(
+                "{name}: period {} cannot be exported to Google Authenticator",
+                account.period
+            )
+            .into())
+        }
+        OtpType::Totp => otp.set_field_type(MigrationPayload_OtpType::OTP_TOTP),
+        OtpType::Hotp => {
+            otp.set_field_type(MigrationPayload_OtpType::OTP_HOTP);
+            otp.set_counter(account.counter as i64);
+        }
+        OtpType::Steam => {
+            return Err(format!("{name}: Steam tokens cannot be exported to Google Authenticator").into())
+        }
+    }
+
+    let alphabet = base32::Alphabet::RFC4648 { padding: false };
+    let secret = base32::decode(alphabet, &account.secret).ok_or(format!("base32::decode failed for {name}"))?;
+
+    let algorithm = match account.sha {
+        Algorithm::SHA1 => MigrationPayload_Algorithm::ALGO_SHA1,
+        Algorithm::SHA256 => MigrationPayload_Algorithm::ALGO_SHA256,
+        Algorithm::SHA512 => MigrationPayload_Algorithm::ALGO_SHA512,
+        Algorithm::MD5 => MigrationPayload_Algorithm::ALGO_MD5,
+    };
+
+    let digits = match account.digits {
+        6 => MigrationPayload_DigitCount::DIGIT_COUNT_SIX,
+        8 => MigrationPayload_DigitCount::DIGIT_COUNT_EIGHT,
+        n => return Err(format!("{name}: {n} digits cannot be exported to Google Authenticator").into()),
+    };
+
+    otp.set_secret(secret);
+    otp.set_name(account.account.to_string());
+    otp.set_issuer(account.issuer.to_string());
+    otp.set_algorithm(algorithm);
+    otp.set_digits(digits);
+    Ok(otp)
 }
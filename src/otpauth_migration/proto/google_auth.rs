@@ -336,7 +336,7 @@ pub struct MigrationPayload_OtpParameters {
     pub name: ::std::string::String,
     pub issuer: ::std::string::String,
     pub algorithm: MigrationPayload_Algorithm,
-    pub digits: i32,
+    pub digits: MigrationPayload_DigitCount,
     pub field_type: MigrationPayload_OtpType,
     pub counter: i64,
     // special fields
@@ -448,18 +448,18 @@ impl MigrationPayload_OtpParameters {
         self.algorithm = v;
     }
 
-    // int32 digits = 5;
+    // .MigrationPayload.DigitCount digits = 5;
 
 
-    pub fn get_digits(&self) -> i32 {
+    pub fn get_digits(&self) -> MigrationPayload_DigitCount {
         self.digits
     }
     pub fn clear_digits(&mut self) {
-        self.digits = 0;
+        self.digits = MigrationPayload_DigitCount::DIGIT_COUNT_UNSPECIFIED;
     }
 
     // Param is passed by value, moved
-    pub fn set_digits(&mut self, v: i32) {
+    pub fn set_digits(&mut self, v: MigrationPayload_DigitCount) {
         self.digits = v;
     }
 
@@ -516,11 +516,7 @@ impl ::protobuf::Message for MigrationPayload_OtpParameters {
                     ::protobuf::rt::read_proto3_enum_with_unknown_fields_into(wire_type, is, &mut self.algorithm, 4, &mut self.unknown_fields)?
                 },
                 5 => {
-                    if wire_type != ::protobuf::wire_format::WireTypeVarint {
-                        return ::std::result::Result::Err(::protobuf::rt::unexpected_wire_type(wire_type));
-                    }
-                    let tmp = is.read_int32()?;
-                    self.digits = tmp;
+                    ::protobuf::rt::read_proto3_enum_with_unknown_fields_into(wire_type, is, &mut self.digits, 5, &mut self.unknown_fields)?
                 },
                 6 => {
                     ::protobuf::rt::read_proto3_enum_with_unknown_fields_into(wire_type, is, &mut self.field_type, 6, &mut self.unknown_fields)?
@@ -556,8 +552,8 @@ impl ::protobuf::Message for MigrationPayload_OtpParameters {
         if self.algorithm != MigrationPayload_Algorithm::ALGO_INVALID {
             my_size += ::protobuf::rt::enum_size(4, self.algorithm);
         }
-        if self.digits != 0 {
-            my_size += ::protobuf::rt::value_size(5, self.digits, ::protobuf::wire_format::WireTypeVarint);
+        if self.digits != MigrationPayload_DigitCount::DIGIT_COUNT_UNSPECIFIED {
+            my_size += ::protobuf::rt::enum_size(5, self.digits);
         }
         if self.field_type != MigrationPayload_OtpType::OTP_INVALID {
             my_size += ::protobuf::rt::enum_size(6, self.field_type);
@@ -583,8 +579,8 @@ impl ::protobuf::Message for MigrationPayload_OtpParameters {
         if self.algorithm != MigrationPayload_Algorithm::ALGO_INVALID {
             os.write_enum(4, ::protobuf::ProtobufEnum::value(&self.algorithm))?;
         }
-        if self.digits != 0 {
-            os.write_int32(5, self.digits)?;
+        if self.digits != MigrationPayload_DigitCount::DIGIT_COUNT_UNSPECIFIED {
+            os.write_enum(5, ::protobuf::ProtobufEnum::value(&self.digits))?;
         }
         if self.field_type != MigrationPayload_OtpType::OTP_INVALID {
             os.write_enum(6, ::protobuf::ProtobufEnum::value(&self.field_type))?;
@@ -650,7 +646,7 @@ impl ::protobuf::Message for MigrationPayload_OtpParameters {
                 |m: &MigrationPayload_OtpParameters| { &m.algorithm },
                 |m: &mut MigrationPayload_OtpParameters| { &mut m.algorithm },
             ));
-            fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeInt32>(
+            fields.push(::protobuf::reflect::accessor::make_simple_field_accessor::<_, ::protobuf::types::ProtobufTypeEnum<MigrationPayload_DigitCount>>(
                 "digits",
                 |m: &MigrationPayload_OtpParameters| { &m.digits },
                 |m: &mut MigrationPayload_OtpParameters| { &mut m.digits },
@@ -685,7 +681,7 @@ impl ::protobuf::Clear for MigrationPayload_OtpParameters {
         self.name.clear();
         self.issuer.clear();
         self.algorithm = MigrationPayload_Algorithm::ALGO_INVALID;
-        self.digits = 0;
+        self.digits = MigrationPayload_DigitCount::DIGIT_COUNT_UNSPECIFIED;
         self.field_type = MigrationPayload_OtpType::OTP_INVALID;
         self.counter = 0;
         self.unknown_fields.clear();
@@ -708,6 +704,9 @@ impl ::protobuf::reflect::ProtobufValue for MigrationPayload_OtpParameters {
 pub enum MigrationPayload_Algorithm {
     ALGO_INVALID = 0,
     ALGO_SHA1 = 1,
+    ALGO_SHA256 = 2,
+    ALGO_SHA512 = 3,
+    ALGO_MD5 = 4,
 }
 
 impl ::protobuf::ProtobufEnum for MigrationPayload_Algorithm {
@@ -719,6 +718,9 @@ impl ::protobuf::ProtobufEnum for MigrationPayload_Algorithm {
         match value {
             0 => ::std::option::Option::Some(MigrationPayload_Algorithm::ALGO_INVALID),
             1 => ::std::option::Option::Some(MigrationPayload_Algorithm::ALGO_SHA1),
+            2 => ::std::option::Option::Some(MigrationPayload_Algorithm::ALGO_SHA256),
+            3 => ::std::option::Option::Some(MigrationPayload_Algorithm::ALGO_SHA512),
+            4 => ::std::option::Option::Some(MigrationPayload_Algorithm::ALGO_MD5),
             _ => ::std::option::Option::None
         }
     }
@@ -727,6 +729,9 @@ impl ::protobuf::ProtobufEnum for MigrationPayload_Algorithm {
         static values: &'static [MigrationPayload_Algorithm] = &[
             MigrationPayload_Algorithm::ALGO_INVALID,
             MigrationPayload_Algorithm::ALGO_SHA1,
+            MigrationPayload_Algorithm::ALGO_SHA256,
+            MigrationPayload_Algorithm::ALGO_SHA512,
+            MigrationPayload_Algorithm::ALGO_MD5,
         ];
         values
     }
@@ -754,6 +759,59 @@ impl ::protobuf::reflect::ProtobufValue for MigrationPayload_Algorithm {
     }
 }
 
+#[derive(Clone,PartialEq,Eq,Debug,Hash)]
+pub enum MigrationPayload_DigitCount {
+    DIGIT_COUNT_UNSPECIFIED = 0,
+    DIGIT_COUNT_SIX = 1,
+    DIGIT_COUNT_EIGHT = 2,
+}
+
+impl ::protobuf::ProtobufEnum for MigrationPayload_DigitCount {
+    fn value(&self) -> i32 {
+        *self as i32
+    }
+
+    fn from_i32(value: i32) -> ::std::option::Option<MigrationPayload_DigitCount> {
+        match value {
+            0 => ::std::option::Option::Some(MigrationPayload_DigitCount::DIGIT_COUNT_UNSPECIFIED),
+            1 => ::std::option::Option::Some(MigrationPayload_DigitCount::DIGIT_COUNT_SIX),
+            2 => ::std::option::Option::Some(MigrationPayload_DigitCount::DIGIT_COUNT_EIGHT),
+            _ => ::std::option::Option::None
+        }
+    }
+
+    fn values() -> &'static [Self] {
+        static values: &'static [MigrationPayload_DigitCount] = &[
+            MigrationPayload_DigitCount::DIGIT_COUNT_UNSPECIFIED,
+            MigrationPayload_DigitCount::DIGIT_COUNT_SIX,
+            MigrationPayload_DigitCount::DIGIT_COUNT_EIGHT,
+        ];
+        values
+    }
+
+    fn enum_descriptor_static() -> &'static ::protobuf::reflect::EnumDescriptor {
+        static descriptor: ::protobuf::rt::LazyV2<::protobuf::reflect::EnumDescriptor> = ::protobuf::rt::LazyV2::INIT;
+        descriptor.get(|| {
+            ::protobuf::reflect::EnumDescriptor::new_pb_name::<MigrationPayload_DigitCount>("MigrationPayload.DigitCount", file_descriptor_proto())
+        })
+    }
+}
+
+impl ::std::marker::Copy for MigrationPayload_DigitCount {
+}
+
+impl ::std::default::Default for MigrationPayload_DigitCount {
+    fn default() -> Self {
+        MigrationPayload_DigitCount::DIGIT_COUNT_UNSPECIFIED
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for MigrationPayload_DigitCount {
+    fn as_ref(&self) -> ::protobuf::reflect::ReflectValueRef {
+        ::protobuf::reflect::ReflectValueRef::Enum(::protobuf::ProtobufEnum::descriptor(self))
+    }
+}
+
 #[derive(Clone,PartialEq,Eq,Debug,Hash)]
 pub enum MigrationPayload_OtpType {
     OTP_INVALID = 0,
@@ -808,21 +866,93 @@ impl ::protobuf::reflect::ProtobufValue for MigrationPayload_OtpType {
 }
 
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\x11google_auth.proto\"\xa7\x04\n\x10MigrationPayload\x12F\n\x0eotp_pa\
+    \n\x11google_auth.proto\"\xcc\x05\n\x10MigrationPayload\x12F\n\x0eotp_pa\
     rameters\x18\x01\x20\x03(\x0b2\x1f.MigrationPayload.OtpParametersR\rotpP\
     arameters\x12\x18\n\x07version\x18\x02\x20\x01(\x05R\x07version\x12\x1d\
     \n\nbatch_size\x18\x03\x20\x01(\x05R\tbatchSize\x12\x1f\n\x0bbatch_index\
     \x18\x04\x20\x01(\x05R\nbatchIndex\x12\x19\n\x08batch_id\x18\x05\x20\x01\
-    (\x05R\x07batchId\x1a\xef\x01\n\rOtpParameters\x12\x16\n\x06secret\x18\
+    (\x05R\x07batchId\x1a\x8d\x02\n\rOtpParameters\x12\x16\n\x06secret\x18\
     \x01\x20\x01(\x0cR\x06secret\x12\x12\n\x04name\x18\x02\x20\x01(\tR\x04na\
     me\x12\x16\n\x06issuer\x18\x03\x20\x01(\tR\x06issuer\x129\n\talgorithm\
-    \x18\x04\x20\x01(\x0e2\x1b.MigrationPayload.AlgorithmR\talgorithm\x12\
-    \x16\n\x06digits\x18\x05\x20\x01(\x05R\x06digits\x12-\n\x04type\x18\x06\
-    \x20\x01(\x0e2\x19.MigrationPayload.OtpTypeR\x04type\x12\x18\n\x07counte\
-    r\x18\x07\x20\x01(\x03R\x07counter\",\n\tAlgorithm\x12\x10\n\x0cALGO_INV\
-    ALID\x10\0\x12\r\n\tALGO_SHA1\x10\x01\"6\n\x07OtpType\x12\x0f\n\x0bOTP_I\
-    NVALID\x10\0\x12\x0c\n\x08OTP_HOTP\x10\x01\x12\x0c\n\x08OTP_TOTP\x10\x02\
-    b\x06proto3\
+    \x18\x04\x20\x01(\x0e2\x1b.MigrationPayload.AlgorithmR\talgorithm\x124\n\
+    \x06digits\x18\x05\x20\x01(\x0e2\x1c.MigrationPayload.DigitCountR\x06dig\
+    its\x12-\n\x04type\x18\x06\x20\x01(\x0e2\x19.MigrationPayload.OtpTypeR\
+    \x04type\x12\x18\n\x07counter\x18\x07\x20\x01(\x03R\x07counter\"\\\n\tAl\
+    gorithm\x12\x10\n\x0cALGO_INVALID\x10\0\x12\r\n\tALGO_SHA1\x10\x01\x12\
+    \x0f\n\x0bALGO_SHA256\x10\x02\x12\x0f\n\x0bALGO_SHA512\x10\x03\x12\x0c\n\
+    \x08ALGO_MD5\x10\x04\"U\n\nDigitCount\x12\x1b\n\x17DIGIT_COUNT_UNSPECIFI\
+    ED\x10\0\x12\x13\n\x0fDIGIT_COUNT_SIX\x10\x01\x12\x15\n\x11DIGIT_COUNT_E\
+    IGHT\x10\x02\"6\n\x07OtpType\x12\x0f\n\x0bOTP_INVALID\x10\0\x12\x0c\n\
+    \x08OTP_HOTP\x10\x01\x12\x0c\n\x08OTP_TOTP\x10\x02J\xe3\x0b\n\x06\x12\
+    \x04\x02\0(\x01\nr\n\x01\x0c\x12\x03\x02\0\x122h\x20Google\x20Authentica\
+    tor\x20export\x20format,\x20google_auth.rs\x20is\x20generated\x20from\
+    \x20this\x20file\x20by\x20rust-protobuf\x202.28.0\n\n\n\n\x02\x04\0\x12\
+    \x04\x04\0(\x01\n\n\n\x03\x04\0\x01\x12\x03\x04\x08\x18\n\x0c\n\x04\x04\
+    \0\x04\0\x12\x04\x05\x02\x0b\x03\n\x0c\n\x05\x04\0\x04\0\x01\x12\x03\x05\
+    \x07\x10\n\r\n\x06\x04\0\x04\0\x02\0\x12\x03\x06\x04\x15\n\x0e\n\x07\x04\
+    \0\x04\0\x02\0\x01\x12\x03\x06\x04\x10\n\x0e\n\x07\x04\0\x04\0\x02\0\x02\
+    \x12\x03\x06\x13\x14\n\r\n\x06\x04\0\x04\0\x02\x01\x12\x03\x07\x04\x12\n\
+    \x0e\n\x07\x04\0\x04\0\x02\x01\x01\x12\x03\x07\x04\r\n\x0e\n\x07\x04\0\
+    \x04\0\x02\x01\x02\x12\x03\x07\x10\x11\n\r\n\x06\x04\0\x04\0\x02\x02\x12\
+    \x03\x08\x04\x14\n\x0e\n\x07\x04\0\x04\0\x02\x02\x01\x12\x03\x08\x04\x0f\
+    \n\x0e\n\x07\x04\0\x04\0\x02\x02\x02\x12\x03\x08\x12\x13\n\r\n\x06\x04\0\
+    \x04\0\x02\x03\x12\x03\t\x04\x14\n\x0e\n\x07\x04\0\x04\0\x02\x03\x01\x12\
+    \x03\t\x04\x0f\n\x0e\n\x07\x04\0\x04\0\x02\x03\x02\x12\x03\t\x12\x13\n\r\
+    \n\x06\x04\0\x04\0\x02\x04\x12\x03\n\x04\x11\n\x0e\n\x07\x04\0\x04\0\x02\
+    \x04\x01\x12\x03\n\x04\x0c\n\x0e\n\x07\x04\0\x04\0\x02\x04\x02\x12\x03\n\
+    \x0f\x10\n\x0c\n\x04\x04\0\x04\x01\x12\x04\r\x02\x11\x03\n\x0c\n\x05\x04\
+    \0\x04\x01\x01\x12\x03\r\x07\x11\n\r\n\x06\x04\0\x04\x01\x02\0\x12\x03\
+    \x0e\x04\x20\n\x0e\n\x07\x04\0\x04\x01\x02\0\x01\x12\x03\x0e\x04\x1b\n\
+    \x0e\n\x07\x04\0\x04\x01\x02\0\x02\x12\x03\x0e\x1e\x1f\n\r\n\x06\x04\0\
+    \x04\x01\x02\x01\x12\x03\x0f\x04\x18\n\x0e\n\x07\x04\0\x04\x01\x02\x01\
+    \x01\x12\x03\x0f\x04\x13\n\x0e\n\x07\x04\0\x04\x01\x02\x01\x02\x12\x03\
+    \x0f\x16\x17\n\r\n\x06\x04\0\x04\x01\x02\x02\x12\x03\x10\x04\x1a\n\x0e\n\
+    \x07\x04\0\x04\x01\x02\x02\x01\x12\x03\x10\x04\x15\n\x0e\n\x07\x04\0\x04\
+    \x01\x02\x02\x02\x12\x03\x10\x18\x19\n\x0c\n\x04\x04\0\x04\x02\x12\x04\
+    \x13\x02\x17\x03\n\x0c\n\x05\x04\0\x04\x02\x01\x12\x03\x13\x07\x0e\n\r\n\
+    \x06\x04\0\x04\x02\x02\0\x12\x03\x14\x04\x14\n\x0e\n\x07\x04\0\x04\x02\
+    \x02\0\x01\x12\x03\x14\x04\x0f\n\x0e\n\x07\x04\0\x04\x02\x02\0\x02\x12\
+    \x03\x14\x12\x13\n\r\n\x06\x04\0\x04\x02\x02\x01\x12\x03\x15\x04\x11\n\
+    \x0e\n\x07\x04\0\x04\x02\x02\x01\x01\x12\x03\x15\x04\x0c\n\x0e\n\x07\x04\
+    \0\x04\x02\x02\x01\x02\x12\x03\x15\x0f\x10\n\r\n\x06\x04\0\x04\x02\x02\
+    \x02\x12\x03\x16\x04\x11\n\x0e\n\x07\x04\0\x04\x02\x02\x02\x01\x12\x03\
+    \x16\x04\x0c\n\x0e\n\x07\x04\0\x04\x02\x02\x02\x02\x12\x03\x16\x0f\x10\n\
+    \x0c\n\x04\x04\0\x03\0\x12\x04\x19\x02!\x03\n\x0c\n\x05\x04\0\x03\0\x01\
+    \x12\x03\x19\n\x17\n\r\n\x06\x04\0\x03\0\x02\0\x12\x03\x1a\x04\x15\n\x0e\
+    \n\x07\x04\0\x03\0\x02\0\x05\x12\x03\x1a\x04\t\n\x0e\n\x07\x04\0\x03\0\
+    \x02\0\x01\x12\x03\x1a\n\x10\n\x0e\n\x07\x04\0\x03\0\x02\0\x03\x12\x03\
+    \x1a\x13\x14\n\r\n\x06\x04\0\x03\0\x02\x01\x12\x03\x1b\x04\x14\n\x0e\n\
+    \x07\x04\0\x03\0\x02\x01\x05\x12\x03\x1b\x04\n\n\x0e\n\x07\x04\0\x03\0\
+    \x02\x01\x01\x12\x03\x1b\x0b\x0f\n\x0e\n\x07\x04\0\x03\0\x02\x01\x03\x12\
+    \x03\x1b\x12\x13\n\r\n\x06\x04\0\x03\0\x02\x02\x12\x03\x1c\x04\x16\n\x0e\
+    \n\x07\x04\0\x03\0\x02\x02\x05\x12\x03\x1c\x04\n\n\x0e\n\x07\x04\0\x03\0\
+    \x02\x02\x01\x12\x03\x1c\x0b\x11\n\x0e\n\x07\x04\0\x03\0\x02\x02\x03\x12\
+    \x03\x1c\x14\x15\n\r\n\x06\x04\0\x03\0\x02\x03\x12\x03\x1d\x04\x1c\n\x0e\
+    \n\x07\x04\0\x03\0\x02\x03\x06\x12\x03\x1d\x04\r\n\x0e\n\x07\x04\0\x03\0\
+    \x02\x03\x01\x12\x03\x1d\x0e\x17\n\x0e\n\x07\x04\0\x03\0\x02\x03\x03\x12\
+    \x03\x1d\x1a\x1b\n\r\n\x06\x04\0\x03\0\x02\x04\x12\x03\x1e\x04\x1a\n\x0e\
+    \n\x07\x04\0\x03\0\x02\x04\x06\x12\x03\x1e\x04\x0e\n\x0e\n\x07\x04\0\x03\
+    \0\x02\x04\x01\x12\x03\x1e\x0f\x15\n\x0e\n\x07\x04\0\x03\0\x02\x04\x03\
+    \x12\x03\x1e\x18\x19\n\r\n\x06\x04\0\x03\0\x02\x05\x12\x03\x1f\x04\x15\n\
+    \x0e\n\x07\x04\0\x03\0\x02\x05\x06\x12\x03\x1f\x04\x0b\n\x0e\n\x07\x04\0\
+    \x03\0\x02\x05\x01\x12\x03\x1f\x0c\x10\n\x0e\n\x07\x04\0\x03\0\x02\x05\
+    \x03\x12\x03\x1f\x13\x14\n\r\n\x06\x04\0\x03\0\x02\x06\x12\x03\x20\x04\
+    \x16\n\x0e\n\x07\x04\0\x03\0\x02\x06\x05\x12\x03\x20\x04\t\n\x0e\n\x07\
+    \x04\0\x03\0\x02\x06\x01\x12\x03\x20\n\x11\n\x0e\n\x07\x04\0\x03\0\x02\
+    \x06\x03\x12\x03\x20\x14\x15\n\x0b\n\x04\x04\0\x02\0\x12\x03#\x02,\n\x0c\
+    \n\x05\x04\0\x02\0\x04\x12\x03#\x02\n\n\x0c\n\x05\x04\0\x02\0\x06\x12\
+    \x03#\x0b\x18\n\x0c\n\x05\x04\0\x02\0\x01\x12\x03#\x19'\n\x0c\n\x05\x04\
+    \0\x02\0\x03\x12\x03#*+\n\x0b\n\x04\x04\0\x02\x01\x12\x03$\x02\x14\n\x0c\
+    \n\x05\x04\0\x02\x01\x05\x12\x03$\x02\x07\n\x0c\n\x05\x04\0\x02\x01\x01\
+    \x12\x03$\x08\x0f\n\x0c\n\x05\x04\0\x02\x01\x03\x12\x03$\x12\x13\n\x0b\n\
+    \x04\x04\0\x02\x02\x12\x03%\x02\x17\n\x0c\n\x05\x04\0\x02\x02\x05\x12\
+    \x03%\x02\x07\n\x0c\n\x05\x04\0\x02\x02\x01\x12\x03%\x08\x12\n\x0c\n\x05\
+    \x04\0\x02\x02\x03\x12\x03%\x15\x16\n\x0b\n\x04\x04\0\x02\x03\x12\x03&\
+    \x02\x18\n\x0c\n\x05\x04\0\x02\x03\x05\x12\x03&\x02\x07\n\x0c\n\x05\x04\
+    \0\x02\x03\x01\x12\x03&\x08\x13\n\x0c\n\x05\x04\0\x02\x03\x03\x12\x03&\
+    \x16\x17\n\x0b\n\x04\x04\0\x02\x04\x12\x03'\x02\x15\n\x0c\n\x05\x04\0\
+    \x02\x04\x05\x12\x03'\x02\x07\n\x0c\n\x05\x04\0\x02\x04\x01\x12\x03'\x08\
+    \x10\n\x0c\n\x05\x04\0\x02\x04\x03\x12\x03'\x13\x14b\x06proto3\
 ";
 
 static file_descriptor_proto_lazy: ::protobuf::rt::LazyV2<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::rt::LazyV2::INIT;
@@ -835,4 +965,4 @@ pub fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescripto
     file_descriptor_proto_lazy.get(|| {
         parse_descriptor_proto()
     })
-}
\ No newline at end of file
+}
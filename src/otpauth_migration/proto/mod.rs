@@ -1 +1,8 @@
+// generated by rust-protobuf 2.28.0, newer compilers flag some of its idioms
+#[allow(
+    unknown_lints,
+    renamed_and_removed_lints,
+    unused_parens,
+    mismatched_lifetime_syntaxes
+)]
 pub mod google_auth;
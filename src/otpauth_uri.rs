@@ -0,0 +1,190 @@
+use crate::totp_token::Account;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// OtpType enum holds the two "host" values of an otpauth URI as per the
+/// [Key URI Format](https://github.com/google/google-authenticator/wiki/Key-Uri-Format)
+/// and Steam Guard, a TOTP shown as 5 characters of Steam's alphabet
+///
+/// Steam is read from "otpauth://steam/..." or "otpauth://totp/...&encoder=steam"
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpType {
+    #[default]
+    Totp,
+    Hotp,
+    Steam,
+}
+
+/// A parsed "otpauth://TYPE/LABEL?PARAMETERS" URI
+///
+/// The label is percent-decoded and split on the first ':' into the optional issuer
+/// prefix and the account name, e.g. "ACME%20Co:john.doe@email.com" yields
+/// `label_issuer = Some("ACME Co")` and `account = "john.doe@email.com"`.
+///
+/// Parameter names are compared case-insensitively and values are percent-decoded.
+#[derive(Debug, PartialEq)]
+pub struct OtpAuthUri {
+    pub otp_type: OtpType,
+    pub label_issuer: Option<String>,
+    pub account: String,
+    params: Vec<(String, String)>,
+}
+
+impl OtpAuthUri {
+    /// Parse an otpauth URI, the "secret" parameter is required
+    ///
+    /// Bare parameters such as "secret=SECRET&digits=8" are read as a TOTP URI without a label
+    pub fn parse(otpauth: &str) -> Result<Self, Box<dyn Error>> {
+        let otpauth = otpauth.trim();
+
+        // Scheme -- "otpauth://", or none for bare parameters
+        let bare;
+        let rest = match otpauth.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("otpauth") => rest,
+            Some((scheme, _)) => return Err(format!("unsupported scheme `{scheme}`, expected `otpauth`").into()),
+            None if otpauth.contains('=') => {
+                bare = format!("totp/?{}", otpauth.trim_start_matches('?'));
+                &bare
+            }
+            None => return Err(format!("not an otpauth URI, otpauth = {otpauth}").into()),
+        };
+
+        // Drop any fragment, then split the path from the query
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        // Type -- "totp", "hotp" or "steam"
+        let (otp_type, label) = path.split_once('/').unwrap_or((path, ""));
+        let mut otp_type = match otp_type {
+            t if t.eq_ignore_ascii_case("totp") => OtpType::Totp,
+            t if t.eq_ignore_ascii_case("hotp") => OtpType::Hotp,
+            t if t.eq_ignore_ascii_case("steam") => OtpType::Steam,
+            "" => return Err(format!("missing OTP type, otpauth = {otpauth}").into()),
+            t => return Err(format!("unsupported OTP type `{t}`, expected `totp`, `hotp` or `steam`").into()),
+        };
+
+        // Label -- "issuer:account" or "account", the colon may also be encoded as "%3A"
+        let label = urlencoding::decode(label).map_err(|e| format!("invalid label encoding: {e}"))?;
+        let (label_issuer, account) = split_label(&label);
+
+        // Parameters -- "name=value" pairs separated by '&'
+        let mut params: Vec<(String, String)> = vec![];
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("malformed parameter `{pair}`, expected name=value"))?;
+            let name = name.to_ascii_lowercase();
+            if name.is_empty() {
+                return Err(format!("malformed parameter `{pair}`, missing name").into());
+            }
+            if params.iter().any(|(n, _)| *n == name) {
+                return Err(format!("duplicate parameter `{name}`").into());
+            }
+            let value = urlencoding::decode(value).map_err(|e| format!("invalid encoding for `{name}`: {e}"))?;
+            params.push((name, value.into()));
+        }
+
+        // Encoder -- KeePassXC and Aegis mark Steam tokens with "encoder=steam"
+        let steam = params
+            .iter()
+            .any(|(name, value)| name == "encoder" && value.eq_ignore_ascii_case("steam"));
+        if otp_type == OtpType::Totp && steam {
+            otp_type = OtpType::Steam;
+        }
+
+        let uri = Self {
+            otp_type,
+            label_issuer,
+            account,
+            params,
+        };
+
+        // Secret -- required
+        match uri.param("secret") {
+            Some(s) if !s.is_empty() => Ok(uri),
+            Some(_) => Err(format!("empty secret, otpauth = {otpauth}").into()),
+            None => Err(format!("missing secret, otpauth = {otpauth}").into()),
+        }
+    }
+
+    /// Return the named parameter value, names are case-insensitive
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Return the issuer parameter, falling back to the label issuer prefix
+    pub fn issuer(&self) -> Option<&str> {
+        self.param("issuer").or(self.label_issuer.as_deref())
+    }
+}
+
+impl FromStr for OtpAuthUri {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl From<&Account> for OtpAuthUri {
+    fn from(account: &Account) -> Self {
+        let mut params = vec![("secret".to_string(), account.secret.to_string())];
+        if !account.issuer.is_empty() {
+            params.push(("issuer".into(), account.issuer.to_string()));
+        }
+        params.push(("algorithm".into(), account.sha.to_string()));
+        params.push(("digits".into(), account.digits.to_string()));
+        match account.otp_type {
+            OtpType::Totp => params.push(("period".into(), account.period.to_string())),
+            OtpType::Hotp => params.push(("counter".into(), account.counter.to_string())),
+            OtpType::Steam => {
+                params.push(("period".into(), account.period.to_string()));
+                params.push(("encoder".into(), "steam".into()));
+            }
+        }
+
+        Self {
+            otp_type: account.otp_type,
+            label_issuer: Some(account.issuer.to_string()).filter(|issuer| !issuer.is_empty()),
+            account: account.account.to_string(),
+            params,
+        }
+    }
+}
+
+/// Canonical form: "otpauth://TYPE/ISSUER:ACCOUNT?secret=...", label and values percent-encoded
+///
+/// Steam tokens are written as "totp" with their "encoder=steam" parameter, the form most apps read
+impl fmt::Display for OtpAuthUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let otp_type = match self.otp_type {
+            OtpType::Totp | OtpType::Steam => "totp",
+            OtpType::Hotp => "hotp",
+        };
+        write!(f, "otpauth://{otp_type}/")?;
+        if let Some(issuer) = &self.label_issuer {
+            write!(f, "{}:", urlencoding::encode(issuer))?;
+        }
+        write!(f, "{}", urlencoding::encode(&self.account))?;
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            let sep = if i == 0 { '?' } else { '&' };
+            write!(f, "{sep}{name}={}", urlencoding::encode(value))?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a decoded label into the optional issuer prefix and the account name
+pub fn split_label(label: &str) -> (Option<String>, String) {
+    match label.split_once(':') {
+        Some((issuer, account)) if !issuer.trim().is_empty() => (Some(issuer.trim().into()), account.trim().into()),
+        Some((_, account)) => (None, account.trim().into()),
+        None => (None, label.trim().into()),
+    }
+}
@@ -0,0 +1,59 @@
+use std::error::Error;
+
+/// Read a password from the file descriptor when given, otherwise prompt on the terminal
+///
+/// Passwords never appear on the command line, where other users could see them in
+/// process listings, e.g. `totp-qr vault --password-fd 3 list 3< ~/.vault-password`
+pub fn read(prompt: &str, fd: Option<i32>) -> Result<String, Box<dyn Error>> {
+    match fd {
+        Some(fd) => read_fd(fd),
+        None => Ok(rpassword::prompt_password(prompt)?),
+    }
+}
+
+/// Read a new password, prompting twice to catch typos
+pub fn read_new(prompt: &str, fd: Option<i32>) -> Result<String, Box<dyn Error>> {
+    let password = read(prompt, fd)?;
+    if fd.is_none() && password != rpassword::prompt_password("Verifying - ".to_string() + prompt)? {
+        return Err("passwords do not match".into());
+    }
+    if password.is_empty() {
+        return Err("empty password".into());
+    }
+    Ok(password)
+}
+
+/// The next line of the file descriptor, without its line ending
+///
+/// The descriptor stays open and is read a byte at a time, so nothing past the line is consumed
+#[cfg(unix)]
+fn read_fd(fd: i32) -> Result<String, Box<dyn Error>> {
+    use std::io::Read;
+    use std::mem::ManuallyDrop;
+    use std::os::fd::FromRawFd;
+
+    if fd < 0 {
+        return Err(format!("could not read password from fd {fd}: not a file descriptor").into());
+    }
+    // SAFETY: the descriptor belongs to the caller and is only borrowed, ManuallyDrop never closes it.
+    // An fd that is not open fails the read with EBADF
+    let mut file = ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+    let mut line = vec![];
+    let mut byte = [0u8];
+    loop {
+        match file.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("could not read password from fd {fd}: {e}").into()),
+        }
+    }
+    let password = String::from_utf8(line).map_err(|_| format!("password from fd {fd} is not UTF-8"))?;
+    Ok(password.trim_end_matches('\r').into())
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: i32) -> Result<String, Box<dyn Error>> {
+    Err("--password-fd is only supported on unix".into())
+}
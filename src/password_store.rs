@@ -0,0 +1,160 @@
+use crate::totp_token::{self, Account};
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The pass store directory, ~/.password-store unless PASSWORD_STORE_DIR is set
+pub fn default_store() -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    PathBuf::from(home.unwrap_or_default()).join(".password-store")
+}
+
+/// Entry names of the store in sorted order, e.g. "otp/github", the path of each `.gpg` file without it
+pub fn entries(store: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    fn walk(store: &Path, dir: &Path, entries: &mut Vec<String>) -> Result<(), Box<dyn Error>> {
+        for dir_entry in fs::read_dir(dir).map_err(|e| format!("could not read `{}`: {e}", dir.display()))? {
+            let path = dir_entry?.path();
+            if path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
+            if path.is_dir() {
+                walk(store, &path, entries)?;
+            } else if path.extension().is_some_and(|ext| ext == "gpg") {
+                let name = path.strip_prefix(store)?.with_extension("");
+                entries.push(name.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        Ok(())
+    }
+
+    let mut entries = vec![];
+    walk(store, store, &mut entries)?;
+    entries.sort();
+    Ok(entries)
+}
+
+/// Decrypt an entry with gpg and decode its otpauth lines the way pass-otp finds them
+///
+/// The entry name fills in a missing issuer, gpg uses `homedir` in place of GNUPGHOME when given
+pub fn read_accounts(store: &Path, entry: &str, homedir: Option<&Path>) -> Result<Vec<Account>, Box<dyn Error>> {
+    let output = gpg(homedir)
+        .args(["--quiet", "--decrypt"])
+        .arg(entry_path(store, entry))
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("could not run gpg: {e}"))?;
+    if !output.status.success() {
+        return Err(format!("gpg could not decrypt `{entry}`").into());
+    }
+
+    let mut accounts = vec![];
+    for line in String::from_utf8(output.stdout)?.lines() {
+        if line.starts_with("otpauth://") {
+            let mut found = totp_token::get_accounts(line).map_err(|e| format!("{entry}: {e}"))?;
+            for account in found.iter_mut().filter(|account| account.issuer.is_empty()) {
+                account.issuer = entry.into();
+            }
+            accounts.extend(found);
+        }
+    }
+    Ok(accounts)
+}
+
+/// Entry name for an account, "PREFIX/ISSUER/ACCOUNT"
+pub fn entry_name(prefix: &str, account: &Account) -> String {
+    let component = |name: &str| {
+        let name = name.replace(['/', '\\'], "_");
+        name.trim_start_matches('.').to_string()
+    };
+    let name = match (component(&account.issuer), component(&account.account)) {
+        (issuer, account) if issuer.is_empty() && account.is_empty() => "account".to_string(),
+        (issuer, account) if issuer.is_empty() => account,
+        (issuer, account) if account.is_empty() => issuer,
+        (issuer, account) => format!("{issuer}/{account}"),
+    };
+    match prefix.trim_matches('/') {
+        "" => name,
+        prefix => format!("{prefix}/{name}"),
+    }
+}
+
+/// Write the otpauth URI of the account as a pass-otp entry, encrypted to the `.gpg-id` recipients
+///
+/// Existing entries are only replaced when `force` is set, gpg uses `homedir` in place of GNUPGHOME when given
+pub fn insert(
+    store: &Path,
+    entry: &str,
+    account: &Account,
+    force: bool,
+    homedir: Option<&Path>,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let path = entry_path(store, entry);
+    if path.exists() && !force {
+        return Err(format!("`{entry}` already exists in the password store, --force replaces it").into());
+    }
+    let dir = path.parent().ok_or("invalid password store entry")?;
+    let recipients = recipients(store, dir)?;
+    fs::create_dir_all(dir)?;
+
+    let mut gpg = gpg(homedir);
+    gpg.args([
+        "--encrypt",
+        "--quiet",
+        "--yes",
+        "--compress-algo=none",
+        "--no-encrypt-to",
+    ]);
+    for recipient in &recipients {
+        gpg.args(["--recipient", recipient]);
+    }
+    let mut child = gpg
+        .arg("--output")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run gpg: {e}"))?;
+    child
+        .stdin
+        .take()
+        .ok_or("could not write to gpg")?
+        .write_all((account.uri() + "\n").as_bytes())?;
+    if !child.wait()?.success() {
+        return Err(format!("gpg could not encrypt `{entry}`").into());
+    }
+    Ok(path)
+}
+
+fn gpg(homedir: Option<&Path>) -> Command {
+    let mut gpg = Command::new("gpg");
+    if let Some(homedir) = homedir {
+        gpg.arg("--homedir").arg(homedir);
+    }
+    gpg
+}
+
+fn entry_path(store: &Path, entry: &str) -> PathBuf {
+    store.join(format!("{entry}.gpg"))
+}
+
+/// Recipients of the nearest `.gpg-id`, from the entry's directory up to the store root
+fn recipients(store: &Path, dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    for dir in dir.ancestors().take_while(|dir| dir.starts_with(store)) {
+        if let Ok(gpg_id) = fs::read_to_string(dir.join(".gpg-id")) {
+            let recipients: Vec<_> = gpg_id
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or_default().trim())
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect();
+            if !recipients.is_empty() {
+                return Ok(recipients);
+            }
+        }
+    }
+    Err(format!("no .gpg-id in `{}`, run `pass init` first", store.display()).into())
+}
@@ -0,0 +1,198 @@
+use image::imageops::{self, FilterType};
+use image::io::Reader as ImageReader;
+use image::{GrayImage, Luma};
+use rqrr::PreparedImage;
+use std::error::Error;
+use std::io::Cursor;
+
+// Larger images are downscaled before the retry attempts, phone photos and 5K screenshots
+// are slow to search and their QR modules are often blurred across many pixels
+const MAX_DIMENSION: u32 = 1600;
+
+/// The grids found in an image and the preprocessing strategy that found them,
+/// its steps joined by " + ", e.g. "downscaled + adaptive threshold + inverted"
+pub struct DecodedImage {
+    pub strategy: String,
+    pub grids: Vec<Result<String, rqrr::DeQRError>>,
+}
+
+/// A preprocessing attempt, the names of its steps in order and the function applying them
+type Strategy = (Vec<String>, Box<dyn Fn(&GrayImage) -> GrayImage>);
+
+/// Decode every QR grid found in image bytes
+///
+/// The image is searched as-is first, when no grid decodes it is retried through
+/// inversion, downscaling, adaptive thresholding, rotation and cropping until one does.
+///
+/// Returns the payload, or the decode failure, of each grid in detection order, e.g.
+///   "otpauth://totp/Site:User?Secret=Base-32&period=30&digits=6&issuer=SiteName"
+///   "otpauth-migration://offline?data=Base-64"
+pub fn decode_grids(bytes: Vec<u8>) -> Result<DecodedImage, Box<dyn Error>> {
+    // Detect the image format and decode the bytes into a Luma image
+    let img = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .decode()?
+        .to_luma8();
+
+    let original = DecodedImage {
+        strategy: "original".into(),
+        grids: detect(img.clone()),
+    };
+    if original.grids.iter().any(Result::is_ok) {
+        return Ok(original);
+    }
+
+    // The retry attempts work on the downscaled image, which is itself the first attempt
+    let mut attempts = strategies();
+    let base = match downscale(&img) {
+        Some(small) => {
+            attempts.insert(0, (vec![], Box::new(GrayImage::clone)));
+            for (steps, _) in &mut attempts {
+                steps.insert(0, "downscaled".into());
+            }
+            small
+        }
+        None => img,
+    };
+
+    for (steps, prepare) in attempts {
+        let grids = detect(prepare(&base));
+        if grids.iter().any(Result::is_ok) {
+            let strategy = steps.join(" + ");
+            return Ok(DecodedImage { strategy, grids });
+        }
+    }
+
+    // Nothing decoded, report what the original image held
+    Ok(original)
+}
+
+/// Prepare for detection, then search for grids and decode each one
+fn detect(img: GrayImage) -> Vec<Result<String, rqrr::DeQRError>> {
+    let mut img = PreparedImage::prepare(img);
+    img.detect_grids()
+        .iter()
+        .map(|grid| grid.decode().map(|(_meta, payload)| payload))
+        .collect()
+}
+
+/// The retry attempts in order
+fn strategies() -> Vec<Strategy> {
+    let mut strategies: Vec<Strategy> = vec![
+        (vec!["inverted".into()], Box::new(inverted)),
+        (vec!["adaptive threshold".into()], Box::new(threshold)),
+        (
+            vec!["adaptive threshold".into(), "inverted".into()],
+            Box::new(|img| inverted(&threshold(img))),
+        ),
+    ];
+    for degrees in [90, 180, 270, 10, -10, 20, -20, 30, -30, 45] {
+        strategies.push((
+            vec![format!("rotated {degrees}°")],
+            Box::new(move |img| rotated(img, degrees)),
+        ));
+    }
+    for (name, x, y) in [
+        ("center", 0.2, 0.2),
+        ("top left", 0.0, 0.0),
+        ("top right", 0.4, 0.0),
+        ("bottom left", 0.0, 0.4),
+        ("bottom right", 0.4, 0.4),
+    ] {
+        strategies.push((
+            vec![format!("cropped {name}")],
+            Box::new(move |img| cropped(img, x, y, 0.6)),
+        ));
+    }
+    strategies
+}
+
+/// Shrink the image so its larger side is MAX_DIMENSION, None when it already fits
+fn downscale(img: &GrayImage) -> Option<GrayImage> {
+    let (width, height) = img.dimensions();
+    let largest = width.max(height);
+    (largest > MAX_DIMENSION).then(|| {
+        let scale = MAX_DIMENSION as f64 / largest as f64;
+        let (w, h) = ((width as f64 * scale) as u32, (height as f64 * scale) as u32);
+        imageops::resize(img, w.max(1), h.max(1), FilterType::Triangle)
+    })
+}
+
+/// Light-on-dark QR codes become dark-on-light
+fn inverted(img: &GrayImage) -> GrayImage {
+    let mut img = img.clone();
+    imageops::invert(&mut img);
+    img
+}
+
+/// Bradley adaptive threshold, a pixel is dark when it is 15% below its neighborhood mean
+///
+/// Evens out uneven lighting and glare in photos of monitors and paper
+fn threshold(img: &GrayImage) -> GrayImage {
+    let (width, height) = img.dimensions();
+    let (w, h) = (width as usize, height as usize);
+
+    // Integral image with a zero row and column
+    let mut integral = vec![0u64; (w + 1) * (h + 1)];
+    for y in 0..h {
+        let mut row = 0u64;
+        for x in 0..w {
+            row += img.get_pixel(x as u32, y as u32)[0] as u64;
+            integral[(y + 1) * (w + 1) + x + 1] = integral[y * (w + 1) + x + 1] + row;
+        }
+    }
+
+    let radius = (w.max(h) / 16).max(4);
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+        let (x1, y1) = ((x + radius + 1).min(w), (y + radius + 1).min(h));
+        let sum = integral[y1 * (w + 1) + x1] + integral[y0 * (w + 1) + x0]
+            - integral[y0 * (w + 1) + x1]
+            - integral[y1 * (w + 1) + x0];
+        let count = ((x1 - x0) * (y1 - y0)) as u64;
+        match (img.get_pixel(x as u32, y as u32)[0] as u64) * count * 100 < sum * 85 {
+            true => Luma([0]),
+            false => Luma([255]),
+        }
+    })
+}
+
+/// Rotate about the center onto a canvas large enough to hold the result, background is white
+fn rotated(img: &GrayImage, degrees: i32) -> GrayImage {
+    match degrees.rem_euclid(360) {
+        90 => return imageops::rotate90(img),
+        180 => return imageops::rotate180(img),
+        270 => return imageops::rotate270(img),
+        _ => {}
+    }
+
+    let (sin, cos) = (degrees as f64).to_radians().sin_cos();
+    let (width, height) = (img.width() as f64, img.height() as f64);
+    let new_width = (width * cos.abs() + height * sin.abs()).ceil();
+    let new_height = (width * sin.abs() + height * cos.abs()).ceil();
+
+    // Map each destination pixel back into the source image, nearest neighbor
+    GrayImage::from_fn(new_width as u32, new_height as u32, |x, y| {
+        let (dx, dy) = (x as f64 - new_width / 2.0, y as f64 - new_height / 2.0);
+        let sx = dx * cos + dy * sin + width / 2.0;
+        let sy = -dx * sin + dy * cos + height / 2.0;
+        match sx >= 0.0 && sy >= 0.0 && sx < width && sy < height {
+            true => *img.get_pixel(sx as u32, sy as u32),
+            false => Luma([255]),
+        }
+    })
+}
+
+/// Crop a `size` fraction of the image starting at the (x, y) fractions
+fn cropped(img: &GrayImage, x: f64, y: f64, size: f64) -> GrayImage {
+    let (width, height) = (img.width() as f64, img.height() as f64);
+    imageops::crop_imm(
+        img,
+        (width * x) as u32,
+        (height * y) as u32,
+        ((width * size) as u32).max(1),
+        ((height * size) as u32).max(1),
+    )
+    .to_image()
+}
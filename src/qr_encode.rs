@@ -0,0 +1,138 @@
+use crate::totp_token::Account;
+use image::{GrayImage, Luma};
+use qrcode::render::{svg, unicode};
+use qrcode::{Color, QrCode};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+// Pixels per QR module and the quiet zone width in modules
+const MODULE_SIZE: u32 = 8;
+const QUIET_ZONE: u32 = 4;
+
+/// Encode the canonical otpauth URI of an account as a QR code
+pub fn qr_code(account: &Account) -> Result<QrCode, Box<dyn Error>> {
+    Ok(QrCode::new(account.uri())?)
+}
+
+/// Write a PNG and an SVG QR image for each account into `dir`, returns the written paths
+///
+/// File names are taken from the issuer and account name, e.g. "Example_alice@google.com.png"
+pub fn write_images(dir: &Path, accounts: &[&Account]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut stems = HashSet::new();
+    let images: Vec<_> = accounts
+        .iter()
+        .map(|account| (unique_stem(account, &mut stems), account.uri()))
+        .collect();
+    write_uri_images(dir, &images)
+}
+
+/// Write a PNG and an SVG QR image for each (file stem, URI) pair into `dir`, returns the written paths
+///
+/// The images hold plaintext secrets, they are created readable by the owner only and an existing
+/// file is an error rather than overwritten
+pub fn write_uri_images(dir: &Path, images: &[(String, String)]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+
+    let mut paths = vec![];
+    for (stem, uri) in images {
+        let code = QrCode::new(uri)?;
+
+        let png = dir.join(format!("{stem}.png"));
+        let mut file = create(&png)?;
+        to_image(&code).write_to(&mut file, image::ImageOutputFormat::Png)?;
+        file.flush()?;
+        paths.push(png);
+
+        let svg = dir.join(format!("{stem}.svg"));
+        let mut file = create(&svg)?;
+        file.write_all(code.render::<svg::Color>().min_dimensions(256, 256).build().as_bytes())?;
+        file.flush()?;
+        paths.push(svg);
+    }
+    Ok(paths)
+}
+
+/// Create a new file with mode 0600, failing when the path exists
+fn create(path: &Path) -> Result<BufWriter<File>, Box<dyn Error>> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path).map_err(|e| match e.kind() {
+        ErrorKind::AlreadyExists => format!("`{}` already exists, not overwriting it", path.display()),
+        _ => format!("could not write `{}`: {e}", path.display()),
+    })?;
+    Ok(BufWriter::new(file))
+}
+
+/// Render the QR code of an account as text for a terminal
+///
+/// Unicode half blocks pack two modules per character cell, the ASCII fallback uses two
+/// characters per module. Colors are swapped for the usual light-on-dark terminal so the
+/// printed glyphs form the light modules and the background forms the dark ones.
+pub fn to_terminal(account: &Account, ascii: bool) -> Result<String, Box<dyn Error>> {
+    let code = qr_code(account)?;
+    Ok(match ascii {
+        true => code
+            .render::<char>()
+            .module_dimensions(2, 1)
+            .dark_color(' ')
+            .light_color('#')
+            .build(),
+        false => code
+            .render::<unicode::Dense1x2>()
+            .dark_color(unicode::Dense1x2::Light)
+            .light_color(unicode::Dense1x2::Dark)
+            .build(),
+    })
+}
+
+/// Render the QR modules into a grayscale image with a quiet zone
+fn to_image(code: &QrCode) -> GrayImage {
+    let width = code.width() as u32;
+    let colors = code.to_colors();
+    let size = (width + 2 * QUIET_ZONE) * MODULE_SIZE;
+    GrayImage::from_fn(size, size, |x, y| {
+        let (mx, my) = (x / MODULE_SIZE, y / MODULE_SIZE);
+        let inside = (QUIET_ZONE..QUIET_ZONE + width).contains(&mx) && (QUIET_ZONE..QUIET_ZONE + width).contains(&my);
+        match inside && colors[((my - QUIET_ZONE) * width + mx - QUIET_ZONE) as usize] == Color::Dark {
+            true => Luma([0]),
+            false => Luma([255]),
+        }
+    })
+}
+
+/// File name stem from the issuer and account name, suffixed with a number when already used
+fn unique_stem(account: &Account, used: &mut HashSet<String>) -> String {
+    let name: Vec<_> = [&account.issuer, &account.account]
+        .into_iter()
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            name.chars()
+                .map(|c| match c.is_alphanumeric() || "@.-+".contains(c) {
+                    true => c,
+                    false => '_',
+                })
+                .collect::<String>()
+        })
+        .collect();
+    let stem = match name.join("_").trim_start_matches('.') {
+        "" => "account".to_string(),
+        stem => stem.to_string(),
+    };
+
+    let mut candidate = stem.clone();
+    for n in 2.. {
+        if used.insert(candidate.clone()) {
+            break;
+        }
+        candidate = format!("{stem}-{n}");
+    }
+    candidate
+}
@@ -0,0 +1,54 @@
+use std::error::Error;
+
+/// Shell syntax of the generated function
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Order of the "token, issuer, account" lines the function prints
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    /// Issuer then account name
+    Issuer,
+    /// Account name
+    Account,
+    /// Vault order
+    None,
+}
+
+impl SortOrder {
+    fn pipe(self) -> &'static str {
+        match self {
+            SortOrder::Issuer => " | sort -t, -k2",
+            SortOrder::Account => " | sort -t, -k3",
+            SortOrder::None => "",
+        }
+    }
+}
+
+/// A shell function embedding the vault text, it decrypts the vault and prints the tokens
+///
+/// Arguments to the function are handed to `totp-qr vault code`, e.g. `totp -v github`
+pub fn render(name: &str, shell: Shell, sort: SortOrder, vault: &str) -> Result<String, Box<dyn Error>> {
+    let mut chars = name.chars();
+    if !chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!("invalid shell function name `{name}`").into());
+    }
+
+    // The vault is JSON of Base-64 strings and numbers, it never holds a quote or an EOF line
+    let vault = vault.trim_end();
+    let sort = sort.pipe();
+    Ok(match shell {
+        Shell::Bash | Shell::Zsh => {
+            format!("\n{name}() {{\ntotp-qr vault --file - code \"$@\" << 'EOF'{sort}\n{vault}\nEOF\n}}\n")
+        }
+        Shell::Fish => {
+            format!("\nfunction {name}\nprintf '%s\\n' '{vault}' | totp-qr vault --file - code $argv{sort}\nend\n")
+        }
+    })
+}
@@ -1,5 +1,7 @@
 use super::*;
-use crate::totp_token::Account;
+use crate::otpauth_uri::OtpType;
+use crate::totp_token::{Account, Algorithm};
+use base64::{engine::general_purpose, Engine as _};
 
 // https://github.com/google/google-authenticator/wiki/Key-Uri-Format
 #[test]
@@ -14,9 +16,12 @@ fn test_totp() -> Result<(), Box<dyn Error>> {
         [Account {
             secret: "HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ".to_string(),
             issuer: "ACME Co".to_string(),
-            sha: "SHA1".to_string(),
+            account: "john.doe@email.com".to_string(),
+            sha: Algorithm::SHA1,
             digits: 6,
-            period: 30
+            period: 30,
+            otp_type: OtpType::Totp,
+            counter: 0
         }]
     );
 
@@ -42,23 +47,32 @@ fn test_migration() -> Result<(), Box<dyn Error>> {
             Account {
                 secret: "JBSWY3DPEHPK3PXP".to_string(),
                 issuer: "Test1".to_string(),
-                sha: "SHA1".to_string(),
+                account: "test1@example1.com".to_string(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             },
             Account {
                 secret: "JBSWY3DPEHPK3PXQ".to_string(),
                 issuer: "Test2".to_string(),
-                sha: "SHA1".to_string(),
+                account: "test2@example2.com".to_string(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             },
             Account {
                 secret: "JBSWY3DPEHPK3PXR".to_string(),
                 issuer: "Test3".to_string(),
-                sha: "SHA1".to_string(),
+                account: "test3@example3.com".to_string(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             }
         ]
     );
@@ -80,7 +94,7 @@ fn test_migration() -> Result<(), Box<dyn Error>> {
 #[test]
 fn test_export_json() -> Result<(), Box<dyn Error>> {
     let otpauth = "otpauth-migration://offline?data=Ci0KCkhlbGxvId6tvu8SEnRlc3QxQGV4YW1wbGUxLmNvbRoFVGVzdDEgASgBMAIKLQoKSGVsbG8h3q2%2B8BISdGVzdDJAZXhhbXBsZTIuY29tGgVUZXN0MiABKAEwAgotCgpIZWxsbyHerb7xEhJ0ZXN0M0BleGFtcGxlMy5jb20aBVRlc3QzIAEoATACEAIYASAA";
-    let json = r#"[{"secret":"JBSWY3DPEHPK3PXP","issuer":"Test1","sha":"SHA1","digits":6,"period":30},{"secret":"JBSWY3DPEHPK3PXQ","issuer":"Test2","sha":"SHA1","digits":6,"period":30},{"secret":"JBSWY3DPEHPK3PXR","issuer":"Test3","sha":"SHA1","digits":6,"period":30}]"#;
+    let json = r#"[{"secret":"JBSWY3DPEHPK3PXP","issuer":"Test1","account":"test1@example1.com","sha":"SHA1","digits":6,"period":30,"otp_type":"totp","counter":0},{"secret":"JBSWY3DPEHPK3PXQ","issuer":"Test2","account":"test2@example2.com","sha":"SHA1","digits":6,"period":30,"otp_type":"totp","counter":0},{"secret":"JBSWY3DPEHPK3PXR","issuer":"Test3","account":"test3@example3.com","sha":"SHA1","digits":6,"period":30,"otp_type":"totp","counter":0}]"#;
     let accounts = totp_token::get_accounts(otpauth)?;
 
     assert_eq!(
@@ -89,23 +103,32 @@ fn test_export_json() -> Result<(), Box<dyn Error>> {
             Account {
                 secret: "JBSWY3DPEHPK3PXP".to_string(),
                 issuer: "Test1".to_string(),
-                sha: "SHA1".to_string(),
+                account: "test1@example1.com".to_string(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             },
             Account {
                 secret: "JBSWY3DPEHPK3PXQ".to_string(),
                 issuer: "Test2".to_string(),
-                sha: "SHA1".to_string(),
+                account: "test2@example2.com".to_string(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             },
             Account {
                 secret: "JBSWY3DPEHPK3PXR".to_string(),
                 issuer: "Test3".to_string(),
-                sha: "SHA1".to_string(),
+                account: "test3@example3.com".to_string(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             }
         ]
     );
@@ -125,26 +148,1176 @@ fn test_import_json() -> Result<(), Box<dyn Error>> {
             Account {
                 secret: "JBSWY3DPEHPK3PXP".to_string(),
                 issuer: "Test1".to_string(),
-                sha: "SHA1".to_string(),
+                account: String::new(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             },
             Account {
                 secret: "JBSWY3DPEHPK3PXQ".to_string(),
                 issuer: "Test2".to_string(),
-                sha: "SHA1".to_string(),
+                account: String::new(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             },
             Account {
                 secret: "JBSWY3DPEHPK3PXR".to_string(),
                 issuer: "Test3".to_string(),
-                sha: "SHA1".to_string(),
+                account: String::new(),
+                sha: Algorithm::SHA1,
                 digits: 6,
-                period: 30
+                period: 30,
+                otp_type: OtpType::Totp,
+                counter: 0
             }
         ]
     );
 
     Ok(())
 }
+
+#[test]
+fn test_otpauth_uri() -> Result<(), Box<dyn Error>> {
+    use crate::otpauth_uri::{OtpAuthUri, OtpType};
+
+    // "secret=" inside the label and an "xsecret" parameter must not be mistaken for the secret
+    let uri =
+        OtpAuthUri::parse("otpauth://totp/mysecret=corp:bob?xsecret=AAAA&SECRET=JBSWY3DPEHPK3PXP&Issuer=ACME%20Co")?;
+    assert_eq!(uri.otp_type, OtpType::Totp);
+    assert_eq!(uri.label_issuer.as_deref(), Some("mysecret=corp"));
+    assert_eq!(uri.account, "bob");
+    assert_eq!(uri.param("secret"), Some("JBSWY3DPEHPK3PXP"));
+    assert_eq!(uri.param("xsecret"), Some("AAAA"));
+    assert_eq!(uri.issuer(), Some("ACME Co"));
+
+    // Encoded colon in the label, the issuer falls back to the label prefix
+    let uri = OtpAuthUri::parse("otpauth://HOTP/ACME%20Co%3A%20john.doe%40email.com?secret=JBSWY3DPEHPK3PXP")?;
+    assert_eq!(uri.otp_type, OtpType::Hotp);
+    assert_eq!(uri.account, "john.doe@email.com");
+    assert_eq!(uri.issuer(), Some("ACME Co"));
+
+    let accounts = totp_token::get_accounts("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP")?;
+    assert_eq!(accounts[0].issuer, "Example");
+    assert_eq!(accounts[0].secret, "JBSWY3DPEHPK3PXP");
+
+    // Bare parameters are a TOTP without a label
+    let accounts = totp_token::get_accounts("secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&digits=8")?;
+    assert_eq!(accounts[0].otp_type, OtpType::Totp);
+    assert_eq!((accounts[0].issuer.as_str(), accounts[0].account.as_str()), ("", ""));
+    assert_eq!(
+        (accounts[0].secret.as_str(), accounts[0].digits),
+        ("HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ", 8)
+    );
+
+    for bad in [
+        "JBSWY3DPEHPK3PXP",
+        "https://totp/Example?secret=JBSWY3DPEHPK3PXP",
+        "otpauth://motp/Example?secret=JBSWY3DPEHPK3PXP",
+        "otpauth:///Example?secret=JBSWY3DPEHPK3PXP",
+        "otpauth://totp/Example?issuer=Example",
+        "otpauth://totp/Example?secret=",
+        "otpauth://totp/Example?secret=JBSWY3DPEHPK3PXP&secret=JBSWY3DPEHPK3PXQ",
+        "otpauth://totp/Example?secret=JBSWY3DPEHPK3PXP&digits",
+    ] {
+        assert!(OtpAuthUri::parse(bad).is_err(), "{bad}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_account_label() -> Result<(), Box<dyn Error>> {
+    // Two logins at one provider are told apart by the label account name
+    let alice = totp_token::get_accounts("otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP")?;
+    let bob = totp_token::get_accounts("otpauth://totp/Example:bob@google.com?secret=JBSWY3DPEHPK3PXP&issuer=Example")?;
+    assert_eq!(
+        (alice[0].issuer.as_str(), alice[0].account.as_str()),
+        ("Example", "alice@google.com")
+    );
+    assert_eq!(
+        (bob[0].issuer.as_str(), bob[0].account.as_str()),
+        ("Example", "bob@google.com")
+    );
+
+    // No issuer anywhere, the label is the account name
+    let accounts = totp_token::get_accounts("otpauth://totp/carol?secret=JBSWY3DPEHPK3PXP")?;
+    assert_eq!(
+        (accounts[0].issuer.as_str(), accounts[0].account.as_str()),
+        ("", "carol")
+    );
+
+    // The account name round-trips through JSON
+    let json = serde_json::to_string(&alice)?;
+    assert_eq!(alice, serde_json::from_str::<Vec<Account>>(&json)?);
+
+    Ok(())
+}
+
+// https://tools.ietf.org/html/rfc4226#appendix-D
+#[test]
+fn test_hotp() -> Result<(), Box<dyn Error>> {
+    // Base-32 of the RFC 4226 secret "12345678901234567890"
+    let otpauth = "otpauth://hotp/ACME:john?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=7";
+    let accounts = totp_token::get_accounts(otpauth)?;
+    assert_eq!(accounts[0].otp_type, OtpType::Hotp);
+    assert_eq!(accounts[0].counter, 7);
+
+    let expected = [
+        "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489",
+    ];
+    for (counter, token) in expected.iter().enumerate() {
+        assert_eq!(totp_token::hotp_token(counter as u64, &accounts[0])?, *token);
+    }
+
+    // The counter is required for HOTP
+    assert!(totp_token::get_accounts("otpauth://hotp/ACME:john?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").is_err());
+
+    // HOTP type and counter survive the migration protobuf
+    let otpauth =
+        "otpauth-migration://offline?data=CioKFDEyMzQ1Njc4OTAxMjM0NTY3ODkwEgRqb2huGgRBQ01FIAEoATABOAcQARgBIAAoAQ%3D%3D";
+    let accounts = totp_token::get_accounts(otpauth)?;
+    assert_eq!((accounts[0].otp_type, accounts[0].counter), (OtpType::Hotp, 7));
+    assert_eq!(totp_token::hotp_token(accounts[0].counter, &accounts[0])?, "162583");
+
+    Ok(())
+}
+
+// RFC 6238 SHA1 test vectors, widened to 7 and 10 digits
+#[test]
+fn test_digits() -> Result<(), Box<dyn Error>> {
+    let otpauth = "otpauth://totp/ACME:john?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    for (digits, expected) in [
+        (7, ["4287082", "7081804", "5353130"]),
+        (10, ["1094287082", "0907081804", "1465353130"]),
+    ] {
+        let accounts = totp_token::get_accounts(&format!("{otpauth}&digits={digits}"))?;
+        assert_eq!(accounts[0].digits, digits);
+        for (time, token) in [59, 1111111109, 20000000000].into_iter().zip(expected) {
+            assert_eq!(totp_token::time_token(time, &accounts[0])?, token);
+        }
+    }
+
+    // Unsupported digit counts are errors, not 6
+    for digits in ["5", "11", "", "eight"] {
+        assert!(totp_token::get_accounts(&format!("{otpauth}&digits={digits}")).is_err());
+    }
+    let mut accounts = totp_token::get_accounts(otpauth)?;
+    accounts[0].digits = 12;
+    assert!(totp_token::time_token(59, &accounts[0]).is_err());
+    Ok(())
+}
+
+// Accounts from JSON, URI's and tokens are validated instead of panicking
+#[test]
+fn test_validate() -> Result<(), Box<dyn Error>> {
+    let mut never = |_: &str| -> Result<String, Box<dyn Error>> { Err("JSON needs no password".into()) };
+    let json = |field: &str, value: serde_json::Value| {
+        let mut account = serde_json::json!({
+            "secret": "JBSWY3DPEHPK3PXP", "issuer": "ACME Co", "account": "bob", "sha": "SHA1", "digits": 6, "period": 30
+        });
+        account[field] = value;
+        format!("[{account}]")
+    };
+
+    for (field, value, reason) in [
+        ("period", serde_json::json!(0), "must be greater than 0"),
+        ("secret", serde_json::json!(""), "must not be empty"),
+        ("secret", serde_json::json!("not base32!"), "must be Base-32"),
+        ("digits", serde_json::json!(4), "4 is not 6 to 10"),
+    ] {
+        let err = formats::import(
+            formats::Format::Json,
+            json(field, value).as_bytes(),
+            &mut never,
+            &Default::default(),
+        )
+        .unwrap_err();
+        let invalid = err
+            .downcast_ref::<totp_token::InvalidAccount>()
+            .ok_or("not an InvalidAccount")?;
+        assert_eq!(
+            (invalid.account.as_str(), invalid.field, invalid.reason.as_str()),
+            ("ACME Co, bob", field, reason)
+        );
+    }
+    assert!(totp_token::get_accounts("otpauth://totp/ACME:bob?secret=JBSWY3DPEHPK3PXP&period=0").is_err());
+    for (field, query) in [("period", "period=abc"), ("counter", "counter=-1")] {
+        let err =
+            totp_token::get_accounts(&format!("otpauth://hotp/ACME:bob?secret=JBSWY3DPEHPK3PXP&{query}")).unwrap_err();
+        let value = query.split_once('=').ok_or("no value")?.1;
+        assert_eq!(
+            err.to_string(),
+            format!(r#"account "ACME, bob": invalid {field}, {value} is not a whole number"#)
+        );
+    }
+
+    // The token functions validate too
+    let mut accounts = totp_token::get_accounts("otpauth://totp/ACME:bob?secret=JBSWY3DPEHPK3PXP")?;
+    accounts[0].period = 0;
+    let err = totp_token::time_token(59, &accounts[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"account "ACME, bob": invalid period, must be greater than 0"#
+    );
+    accounts[0].period = 30;
+    accounts[0].secret.clear();
+    assert!(totp_token::hotp_token(0, &accounts[0]).is_err());
+    Ok(())
+}
+
+// Common algorithm spellings are read, the JSON of earlier versions is written
+#[test]
+fn test_algorithm() -> Result<(), Box<dyn Error>> {
+    for name in ["SHA256", "sha256", "sha-256", "SHA_256", "HmacSHA256", "HMAC-SHA-256"] {
+        assert_eq!(name.parse::<Algorithm>()?, Algorithm::SHA256);
+    }
+    assert_eq!("sha1".parse::<Algorithm>()?, Algorithm::SHA1);
+    assert_eq!("HmacMD5".parse::<Algorithm>()?, Algorithm::MD5);
+    assert!("SHA-257".parse::<Algorithm>().is_err());
+    assert!("".parse::<Algorithm>().is_err());
+
+    let json = r#"[{"secret":"JBSWY3DPEHPK3PXP","issuer":"Test1","sha":"sha-512","digits":6,"period":30}]"#;
+    let accounts: Vec<Account> = serde_json::from_str(json)?;
+    assert_eq!(accounts[0].sha, Algorithm::SHA512);
+    assert!(serde_json::to_string(&accounts)?.contains(r#""sha":"SHA512""#));
+
+    // Typos are errors instead of SHA1
+    let json = json.replace("sha-512", "SHA-257");
+    let err = serde_json::from_str::<Vec<Account>>(&json).unwrap_err();
+    assert!(err.to_string().starts_with("unsupported algorithm `SHA-257`"));
+    assert!(totp_token::get_accounts("otpauth://totp/ACME:bob?secret=JBSWY3DPEHPK3PXP&algorithm=SHA-257").is_err());
+    assert_eq!(
+        totp_token::get_accounts("otpauth://totp/ACME:bob?secret=JBSWY3DPEHPK3PXP&algorithm=sha-256")?[0].sha,
+        Algorithm::SHA256
+    );
+    Ok(())
+}
+
+#[test]
+fn test_account_uri() -> Result<(), Box<dyn Error>> {
+    let otpauth =
+        "otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME%20Co";
+    let accounts = totp_token::get_accounts(otpauth)?;
+    assert_eq!(
+        accounts[0].uri(),
+        "otpauth://totp/ACME%20Co:john.doe%40email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME%20Co&algorithm=SHA1&digits=6&period=30"
+    );
+
+    // Canonical URIs decode back into the same accounts
+    for otpauth in [
+        otpauth,
+        "otpauth://hotp/carol?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=7&digits=8",
+        "otpauth-migration://offline?data=Ci0KCkhlbGxvId6tvu8SEnRlc3QxQGV4YW1wbGUxLmNvbRoFVGVzdDEgASgBMAIKLQoKSGVsbG8h3q2%2B8BISdGVzdDJAZXhhbXBsZTIuY29tGgVUZXN0MiABKAEwAgotCgpIZWxsbyHerb7xEhJ0ZXN0M0BleGFtcGxlMy5jb20aBVRlc3QzIAEoATACEAIYASAA",
+    ] {
+        for account in totp_token::get_accounts(otpauth)? {
+            assert_eq!(totp_token::get_accounts(&account.uri())?, [account]);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_qr_images() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("totp-qr-test-{}", std::process::id()));
+    let accounts = totp_token::get_accounts("otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP")?;
+    let paths = qr_encode::write_images(&dir, &[&accounts[0], &accounts[0]])?;
+    let names: Vec<_> = paths.iter().filter_map(|p| p.file_name()?.to_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "Example_alice@google.com.png",
+            "Example_alice@google.com.svg",
+            "Example_alice@google.com-2.png",
+            "Example_alice@google.com-2.svg"
+        ]
+    );
+
+    // The written PNG decodes back into the canonical URI
+    let grids = qr_decode::decode_grids(std::fs::read(&paths[0])?)?.grids;
+    assert_eq!(grids.len(), 1);
+    assert_eq!(grids[0].as_ref().ok(), Some(&accounts[0].uri()));
+
+    // Images hold the secret, they are private and never overwritten
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        for path in &paths {
+            assert_eq!(std::fs::metadata(path)?.permissions().mode() & 0o777, 0o600);
+        }
+    }
+    let png = std::fs::read(&paths[0])?;
+    let other = totp_token::get_accounts("otpauth://totp/Example:alice@google.com?secret=GEZDGNBVGY3TQOJQ")?;
+    let err = qr_encode::write_images(&dir, &[&other[0]]).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("Example_alice@google.com.png` already exists, not overwriting it"));
+    assert_eq!(std::fs::read(&paths[0])?, png);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_terminal_qr() -> Result<(), Box<dyn Error>> {
+    let accounts = totp_token::get_accounts("otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP")?;
+    let width = qr_encode::qr_code(&accounts[0])?.width();
+
+    // Half blocks: two module rows per line, plus a 4 module quiet zone on every side
+    let unicode = qr_encode::to_terminal(&accounts[0], false)?;
+    assert_eq!(unicode.lines().count(), (width + 8).div_ceil(2));
+    assert!(unicode.lines().all(|line| line.chars().count() == width + 8));
+
+    // ASCII: one module row per line, two characters per module
+    let ascii = qr_encode::to_terminal(&accounts[0], true)?;
+    assert!(ascii.is_ascii());
+    assert_eq!(ascii.lines().count(), width + 8);
+    assert!(ascii.lines().all(|line| line.len() == 2 * (width + 8)));
+
+    Ok(())
+}
+
+#[test]
+fn test_migration_export() -> Result<(), Box<dyn Error>> {
+    // 25 accounts span 3 batches of at most 10
+    let accounts: Vec<_> = (0..25)
+        .map(|i| Account {
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            issuer: format!("Issuer{i}"),
+            account: format!("user{i}@example.com"),
+            sha: Algorithm::SHA1,
+            digits: if i % 2 == 0 { 6 } else { 8 },
+            period: 30,
+            otp_type: if i == 3 { OtpType::Hotp } else { OtpType::Totp },
+            counter: if i == 3 { 42 } else { 0 },
+        })
+        .collect();
+    let refs: Vec<_> = accounts.iter().collect();
+    let uris = otpauth_migration::to_uris(&refs, 12345)?;
+    assert_eq!(uris.len(), 3);
+
+    let mut decoded = vec![];
+    for uri in &uris {
+        assert!(uri.starts_with("otpauth-migration://offline?data="));
+        decoded.extend(totp_token::get_accounts(uri)?);
+    }
+    assert_eq!(decoded, accounts);
+
+    // Authenticator has no period choice beyond 30 seconds
+    let period60 = Account {
+        period: 60,
+        ..totp_token::get_accounts("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP")?.remove(0)
+    };
+    assert!(otpauth_migration::to_uris(&[&period60], 1).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_migration_batches() -> Result<(), Box<dyn Error>> {
+    let accounts: Vec<_> = (0..25)
+        .map(|i| Account {
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            issuer: format!("Issuer{i}"),
+            account: String::new(),
+            sha: Algorithm::SHA1,
+            digits: 6,
+            period: 30,
+            otp_type: OtpType::Totp,
+            counter: 0,
+        })
+        .collect();
+    let refs: Vec<_> = accounts.iter().collect();
+    let first = otpauth_migration::to_uris(&refs, 7)?;
+    let second = otpauth_migration::to_uris(&refs[..5], 8)?;
+
+    // Export 7 is missing its middle QR code and has its first one scanned twice, export 8 is complete
+    let uris = [&first[0], &first[0], &first[2], &second[0]].map(String::as_str);
+    let batches = otpauth_migration::group_batches(uris)?;
+    assert_eq!(batches.len(), 2);
+    assert_eq!((batches[0].batch_id, batches[0].batch_size), (7, 3));
+    assert_eq!(batches[0].missing(), [1]);
+    assert!(batches[0].repeated.is_empty());
+    assert!(!batches[0].is_complete());
+    assert_eq!(batches[0].batches[&2], accounts[20..]);
+    assert!(batches[1].is_complete());
+
+    // A batch_index claimed by two different payloads of the same export
+    let other = otpauth_migration::to_uris(&refs[10..], 7)?;
+    let batches = otpauth_migration::group_batches([&first[0], &other[0]].map(String::as_str))?;
+    assert_eq!(batches[0].repeated, [0]);
+
+    Ok(())
+}
+
+// https://tools.ietf.org/html/rfc6238#appendix-B
+#[test]
+fn test_migration_algorithms() -> Result<(), Box<dyn Error>> {
+    let alphabet = base32::Alphabet::RFC4648 { padding: false };
+    let account = |seed: &str, sha: Algorithm| Account {
+        secret: base32::encode(alphabet, seed.as_bytes()),
+        issuer: sha.to_string(),
+        account: "rfc6238".to_string(),
+        sha,
+        digits: 8,
+        period: 30,
+        otp_type: OtpType::Totp,
+        counter: 0,
+    };
+    let accounts = [
+        account("12345678901234567890", Algorithm::SHA1),
+        account("12345678901234567890123456789012", Algorithm::SHA256),
+        account(
+            "1234567890123456789012345678901234567890123456789012345678901234",
+            Algorithm::SHA512,
+        ),
+    ];
+    let refs: Vec<_> = accounts.iter().collect();
+    let uris = otpauth_migration::to_uris(&refs, 1)?;
+    let decoded = totp_token::get_accounts(&uris[0])?;
+    assert_eq!(decoded, accounts);
+
+    let tokens: Vec<_> = decoded
+        .iter()
+        .map(|account| totp_token::time_token(59, account))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(tokens, ["94287082", "46119246", "90693936"]);
+
+    // MD5 decodes but is refused, its 16 byte digest is too short for offsets above 12
+    let md5 = account("12345678901234567890", Algorithm::MD5);
+    let uris = otpauth_migration::to_uris(&[&md5], 1)?;
+    let err = totp_token::get_accounts(&uris[0]).unwrap_err().to_string();
+    assert!(err.contains("invalid algorithm, MD5 is not supported"), "{err}");
+    assert!(totp_token::time_token(59, &md5).is_err());
+
+    // An algorithm value beyond the proto definitions is refused instead of falling back to SHA1
+    // OtpParameters { secret: "Hello!", name: "x", algorithm: 9 }
+    let otpauth = "otpauth-migration://offline?data=Cg8KBkhlbGxvIRIBeCAJKAE%3D";
+    assert!(totp_token::get_accounts(otpauth).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_multiple_grids() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("totp-qr-grids-{}", std::process::id()));
+    let payloads = [
+        "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP",
+        "https://example.com/setup",
+        "otpauth://totp/Example:bob?secret=JBSWY3DPEHPK3PXQ",
+    ];
+    let images: Vec<_> = payloads
+        .iter()
+        .enumerate()
+        .map(|(i, payload)| (format!("grid{i}"), payload.to_string()))
+        .collect();
+    let paths = qr_encode::write_uri_images(&dir, &images)?;
+
+    // Place the three PNG QR codes side by side in one "screenshot"
+    let pngs: Vec<_> = paths
+        .iter()
+        .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+        .map(|p| Ok(image::open(p)?.to_luma8()))
+        .collect::<Result<_, Box<dyn Error>>>()?;
+    let (width, height) = pngs[0].dimensions();
+    let mut sheet = image::GrayImage::from_pixel(width * 3, height, image::Luma([255]));
+    for (i, png) in pngs.iter().enumerate() {
+        image::imageops::replace(&mut sheet, png, (i as u32 * width) as i64, 0);
+    }
+    let mut bytes = vec![];
+    sheet.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
+
+    let mut decoded: Vec<_> = qr_decode::decode_grids(bytes)?
+        .grids
+        .into_iter()
+        .collect::<Result<_, _>>()?;
+    decoded.sort();
+    let mut expected = payloads.map(String::from);
+    expected.sort();
+    assert_eq!(decoded, expected);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_preprocessing() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("totp-qr-prep-{}", std::process::id()));
+    let accounts = totp_token::get_accounts("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP")?;
+    let paths = qr_encode::write_images(&dir, &[&accounts[0]])?;
+    let qr = image::open(&paths[0])?.to_luma8();
+    let png = |img: &image::GrayImage| -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut bytes = vec![];
+        img.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
+        Ok(bytes)
+    };
+
+    // A plain QR code needs no preprocessing
+    let decoded = qr_decode::decode_grids(png(&qr)?)?;
+    assert_eq!(decoded.strategy, "original");
+
+    // Light-on-dark QR code
+    let mut inverted = qr.clone();
+    image::imageops::invert(&mut inverted);
+    let decoded = qr_decode::decode_grids(png(&inverted)?)?;
+    assert_eq!(decoded.strategy, "inverted");
+    assert_eq!(decoded.grids[0].as_ref().ok(), Some(&accounts[0].uri()));
+
+    // Light-on-dark QR code in a very large screenshot
+    let (width, height) = inverted.dimensions();
+    let large = image::imageops::resize(&inverted, width * 6, height * 6, image::imageops::FilterType::Nearest);
+    let decoded = qr_decode::decode_grids(png(&large)?)?;
+    assert_eq!(decoded.strategy, "downscaled + inverted");
+    assert_eq!(decoded.grids[0].as_ref().ok(), Some(&accounts[0].uri()));
+
+    // Lighting fading across the code, a small QR code at 45°, a low contrast code in a bright frame
+    for (file, strategy) in [
+        ("testdata/qr-uneven-lighting.png", "adaptive threshold"),
+        ("testdata/qr-rotated.png", "rotated 20°"),
+        ("testdata/qr-framed.png", "cropped center"),
+    ] {
+        let decoded = qr_decode::decode_grids(std::fs::read(file)?)?;
+        assert_eq!(decoded.strategy, strategy, "{file}");
+        assert_eq!(decoded.grids[0].as_ref().ok(), Some(&accounts[0].uri()), "{file}");
+    }
+
+    // Nothing to find
+    let blank = image::GrayImage::from_pixel(64, 64, image::Luma([255]));
+    assert!(qr_decode::decode_grids(png(&blank)?)?.grids.is_empty());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_vault() -> Result<(), Box<dyn Error>> {
+    let otpauth = "otpauth-migration://offline?data=Ci0KCkhlbGxvId6tvu8SEnRlc3QxQGV4YW1wbGUxLmNvbRoFVGVzdDEgASgBMAIKLQoKSGVsbG8h3q2%2B8BISdGVzdDJAZXhhbXBsZTIuY29tGgVUZXN0MiABKAEwAgotCgpIZWxsbyHerb7xEhJ0ZXN0M0BleGFtcGxlMy5jb20aBVRlc3QzIAEoATACEAIYASAA";
+    let accounts = totp_token::get_accounts(otpauth)?;
+
+    // Cheap Argon2id parameters keep the test fast
+    let sealed = vault::seal(&accounts, "foo", vault::KdfParams::with_cost(256, 1, 1))?;
+    assert!(!sealed.contains("JBSWY3DPEHPK3PXP"));
+    assert!(!sealed.contains("Test1"));
+    assert_eq!(vault::open(&sealed, "foo")?, accounts);
+    assert!(vault::open(&sealed, "bar").is_err());
+
+    // The key derivation parameters are authenticated
+    let tampered = sealed.replace("\"iterations\": 1", "\"iterations\": 2");
+    assert_ne!(tampered, sealed);
+    assert!(vault::open(&tampered, "foo").is_err());
+
+    // Each seal uses a fresh salt and nonce
+    assert_ne!(
+        sealed,
+        vault::seal(&accounts, "foo", vault::KdfParams::with_cost(256, 1, 1))?
+    );
+
+    // Rewriting replaces the vault through a fresh owner-only file, "accounts.tmp" is left alone
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("totp-qr-vault-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("accounts.vault");
+        std::fs::write(&path, "")?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+        std::fs::write(dir.join("accounts.tmp"), "unrelated")?;
+        vault::write(&path, &accounts, "foo")?;
+        assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        assert_eq!(vault::read(&path, "foo")?, accounts);
+        assert_eq!(std::fs::read_to_string(dir.join("accounts.tmp"))?, "unrelated");
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 2);
+        std::fs::remove_dir_all(&dir)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_password_fd() -> Result<(), Box<dyn Error>> {
+    use std::io::Seek;
+    use std::os::fd::AsRawFd;
+
+    // The descriptor is borrowed, each read takes the next line and it stays open afterwards
+    let path = std::env::temp_dir().join(format!("totp-qr-password-fd-{}", std::process::id()));
+    std::fs::write(&path, "foo\nbar\r\n")?;
+    let mut file = std::fs::File::open(&path)?;
+    let fd = Some(file.as_raw_fd());
+    assert_eq!(password::read("", fd)?, "foo");
+    assert_eq!(password::read("", fd)?, "bar");
+    assert_eq!(password::read("", fd)?, "");
+    file.rewind()?;
+    assert_eq!(password::read("", fd)?, "foo");
+    drop(file);
+    std::fs::remove_file(&path)?;
+
+    assert!(password::read("", Some(-1)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_openssl_enc() -> Result<(), Box<dyn Error>> {
+    // `totp-qr --uri images/* | openssl aes-256-cbc -e -pbkdf2 -a` (password: foo) from the README
+    let blob = "U2FsdGVkX18lfKZ20uQn/AcAWa85hUmcJzQ8mvS9JX0BJb7qVDddrCjbjPxagIw6
+hwHeLBPWx1U0GbA7zszAYKNa6FB2I53ldNET/tnutUBNmQeuxqbiVH8A0or9Ni8+
+Lj8onivfmaGzcBGGGMtz3wliD/LL+iUhkG+A2FZpIE2mIf9QdwofI9jSAhDhAW3y
+d+AXZWWsHRRVs5MvIA++CcchKLG+FOza3fcBIt7RtqkdISQYDw+TgMGLN8NS5/ak
+tk8PcuO+QfjmtNXh0/96mn5jYCGdD1NvioeDkwBu7883q2ChHXcOLRuPqqlJAR/2
+T+DwgtEyCO5ZhQPn3nj9E1Gy1xXAm+4Yt8CueXvuBS5SJJLQd94Q+HT1SsyMhYB0
+FGVb6YifAjV3Snsk3UO/60quJ8cfQxjDW5Pef/a0LjtMZL2d+jaYImFcLEMUrnlI
+FRGDcbHR1oAmdonyuSNJBQ==
+";
+    assert!(openssl_enc::is_salted(blob.as_bytes()));
+    let plaintext = String::from_utf8(openssl_enc::decrypt(blob.as_bytes(), "foo")?)?;
+    let accounts: Vec<_> = plaintext
+        .lines()
+        .map(totp_token::get_accounts)
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    let names: Vec<_> = accounts.iter().map(Account::name).collect();
+    assert_eq!(
+        names,
+        [
+            "Test1, test1@example1.com",
+            "Test2, test2@example2.com",
+            "Test3, test3@example3.com",
+            "Example, alice@google.com"
+        ]
+    );
+    assert!(openssl_enc::decrypt(blob.as_bytes(), "bar").is_err());
+
+    // Single line Base-64, `openssl aes-256-cbc -e -pbkdf2 -a -A`
+    let blob = "U2FsdGVkX181d0lkG5ow9UxbHmXnnvniVBfya4WpD4n4Y3j9rCL+n0QSwooFm1FFpWtVEySHwcoh48d0o3YzAtNw2jSsJsYmRrGBXsedupb7VtbqEgmZn7V3+7PvMVbxiM71e42Y1CR6vbPTrH04vA==";
+    let plaintext = openssl_enc::decrypt(blob.as_bytes(), "foo")?;
+    assert_eq!(
+        std::str::from_utf8(&plaintext)?,
+        "otpauth://totp/Example:alice@google.com?issuer=Example&period=30&secret=JBSWY3DPEHPK3PXP\n"
+    );
+
+    // Binary, `openssl aes-256-cbc -e -pbkdf2`
+    let binary = general_purpose::STANDARD.decode(blob)?;
+    assert!(openssl_enc::is_salted(&binary));
+    assert_eq!(openssl_enc::decrypt(&binary, "foo")?, plaintext);
+
+    assert!(!openssl_enc::is_salted(
+        b"otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_shell_function() -> Result<(), Box<dyn Error>> {
+    use shell_function::{Shell, SortOrder};
+
+    let accounts = totp_token::get_accounts("otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP")?;
+    let sealed = vault::seal(&accounts, "foo", vault::KdfParams::with_cost(256, 1, 1))?;
+
+    let bash = shell_function::render("totp", Shell::Bash, SortOrder::Issuer, &sealed)?;
+    assert!(bash.starts_with("\ntotp() {\ntotp-qr vault --file - code \"$@\" << 'EOF' | sort -t, -k2\n{\n"));
+    assert!(bash.ends_with("}\nEOF\n}\n"));
+    assert!(!bash.contains("JBSWY3DPEHPK3PXP"));
+
+    // The embedded vault decrypts back to the accounts
+    let embedded = bash.split("-k2\n").nth(1).and_then(|rest| rest.split("\nEOF\n").next());
+    let embedded = embedded.ok_or("no vault in the bash function")?;
+    assert_eq!(vault::open(embedded, "foo")?, accounts);
+
+    let zsh = shell_function::render("otp", Shell::Zsh, SortOrder::None, &sealed)?;
+    assert!(zsh.starts_with("\notp() {\ntotp-qr vault --file - code \"$@\" << 'EOF'\n{\n"));
+
+    let fish = shell_function::render("totp", Shell::Fish, SortOrder::Account, &sealed)?;
+    assert!(fish.starts_with("\nfunction totp\nprintf '%s\\n' '{\n"));
+    assert!(fish.ends_with("}' | totp-qr vault --file - code $argv | sort -t, -k3\nend\n"));
+
+    for name in ["", "1totp", "totp; rm", "to tp", "$(id)"] {
+        assert!(shell_function::render(name, Shell::Bash, SortOrder::Issuer, &sealed).is_err());
+    }
+    Ok(())
+}
+
+// The accounts most importer fixtures hold: a SHA256 TOTP with 8 digits and a 60 second period
+// second, an HOTP at counter 5 third, encrypted fixtures use the password "foo"
+const FIXTURE_NAMES: [&str; 4] = [
+    "Example, alice@google.com",
+    "ACME Co, bob",
+    "Counter, carol",
+    "Steam, dave",
+];
+
+fn no_password(_: &str) -> Result<String, Box<dyn Error>> {
+    Err("plain files need no password".into())
+}
+
+fn import_plain(format: formats::Format, bytes: &[u8]) -> Result<Vec<Account>, Box<dyn Error>> {
+    formats::import(format, bytes, &mut no_password, &Default::default())
+}
+
+fn import_with(format: formats::Format, bytes: &[u8], password: &str) -> Result<Vec<Account>, Box<dyn Error>> {
+    let mut password = |_: &str| -> Result<String, Box<dyn Error>> { Ok(password.into()) };
+    formats::import(format, bytes, &mut password, &Default::default())
+}
+
+fn assert_fixture(accounts: &[Account], names: &[&str]) {
+    assert_eq!(accounts.iter().map(Account::name).collect::<Vec<_>>(), names);
+    assert_eq!(
+        (accounts[1].sha, accounts[1].digits, accounts[1].period),
+        (Algorithm::SHA256, 8, 60)
+    );
+    assert_eq!((accounts[2].otp_type, accounts[2].counter), (OtpType::Hotp, 5));
+}
+
+// "foo" opens the file, "bar" and a file cut in half are errors and never panic
+fn assert_encrypted(format: formats::Format, encrypted: &[u8], accounts: &[Account]) -> Result<(), Box<dyn Error>> {
+    assert_eq!(import_with(format, encrypted, "foo")?, accounts);
+    let err = import_with(format, encrypted, "bar").unwrap_err();
+    assert!(err.to_string().contains("wrong password"), "{err}");
+    assert!(import_with(format, &encrypted[..encrypted.len() / 2], "foo").is_err());
+    Ok(())
+}
+
+// Exported files import back as the same accounts
+fn assert_round_trip(format: formats::Format, accounts: &[Account]) -> Result<(), Box<dyn Error>> {
+    let exported = formats::export(format, &accounts.iter().collect::<Vec<_>>(), &mut no_password)?;
+    assert_eq!(import_plain(format, &exported)?, accounts);
+    Ok(())
+}
+
+#[test]
+fn test_aegis() -> Result<(), Box<dyn Error>> {
+    let accounts = import_plain(formats::Format::Aegis, include_bytes!("../testdata/aegis-plain.json"))?;
+    assert_fixture(&accounts, &FIXTURE_NAMES);
+    assert_eq!(
+        accounts[1].secret,
+        "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA"
+    );
+    assert_eq!(totp_token::hotp_token(accounts[2].counter, &accounts[2])?, "254676");
+    assert_eq!(accounts[3].otp_type, OtpType::Steam);
+
+    // Encrypted backup, scrypt key slot
+    let encrypted = include_bytes!("../testdata/aegis-encrypted.json");
+    assert_encrypted(formats::Format::Aegis, encrypted, &accounts)?;
+    assert_round_trip(formats::Format::Aegis, &accounts)?;
+
+    // A corrupted database is not blamed on the password
+    let mut corrupted: serde_json::Value = serde_json::from_slice(encrypted)?;
+    let mut db = general_purpose::STANDARD.decode(corrupted["db"].as_str().ok_or("no db")?)?;
+    db[0] ^= 1;
+    corrupted["db"] = general_purpose::STANDARD.encode(db).into();
+    let err = import_with(formats::Format::Aegis, corrupted.to_string().as_bytes(), "foo").unwrap_err();
+    assert_eq!(err.to_string(), "Aegis database decryption failed");
+
+    // Entries of unsupported types are skipped
+    let entry = |entry_type, name| {
+        serde_json::json!({
+            "type": entry_type, "uuid": "", "name": name, "issuer": "Example",
+            "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "SHA1", "digits": 6}
+        })
+    };
+    let backup = serde_json::json!({
+        "version": 1,
+        "header": {"slots": null, "params": null},
+        "db": {"version": 2, "entries": [entry("yandex", "bob"), entry("motp", "carol"), entry("totp", "alice")]}
+    });
+    let skipped = import_plain(formats::Format::Aegis, backup.to_string().as_bytes())?;
+    assert_eq!(
+        skipped.iter().map(Account::name).collect::<Vec<_>>(),
+        ["Example, alice"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_2fas() -> Result<(), Box<dyn Error>> {
+    // The service name stands in for a missing issuer
+    let accounts = import_plain(formats::Format::TwoFas, include_bytes!("../testdata/2fas-plain.2fas"))?;
+    assert_fixture(&accounts, &FIXTURE_NAMES);
+
+    // servicesEncrypted, PBKDF2 and AES-GCM
+    let encrypted = include_bytes!("../testdata/2fas-encrypted.2fas");
+    assert_encrypted(formats::Format::TwoFas, encrypted, &accounts)?;
+    assert_round_trip(formats::Format::TwoFas, &accounts)?;
+
+    // Services of unsupported token types are skipped
+    let yandex = br#"{"services": [
+        {"name": "Yandex", "secret": "JBSWY3DPEHPK3PXP", "otp": {"tokenType": "YAOTP"}},
+        {"name": "Example", "secret": "JBSWY3DPEHPK3PXP", "otp": {"account": "alice"}}
+    ]}"#;
+    let skipped = import_plain(formats::Format::TwoFas, yandex)?;
+    assert_eq!(
+        skipped.iter().map(Account::name).collect::<Vec<_>>(),
+        ["Example, alice"]
+    );
+    assert!(import_plain(formats::Format::TwoFas, b"[]").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_andotp_freeotp() -> Result<(), Box<dyn Error>> {
+    // "ISSUER - ACCOUNT" labels of old backups are split
+    let accounts = import_plain(formats::Format::AndOtp, include_bytes!("../testdata/andotp-plain.json"))?;
+    assert_fixture(&accounts, &FIXTURE_NAMES);
+
+    // PBKDF2 and AES-GCM, a file shorter than its header is not taken for an encrypted backup
+    let encrypted = include_bytes!("../testdata/andotp-encrypted.json.aes");
+    assert_encrypted(formats::Format::AndOtp, encrypted, &accounts)?;
+    let err = import_with(formats::Format::AndOtp, &encrypted[..20], "foo").unwrap_err();
+    assert_eq!(err.to_string(), "not an andOTP backup, too short to be encrypted");
+    assert!(formats::export(formats::Format::AndOtp, &[], &mut no_password).is_err());
+
+    // Entries of unsupported types are skipped
+    let motp = br#"[
+        {"secret": "JBSWY3DPEHPK3PXP", "label": "Mobile - alice", "type": "MOTP"},
+        {"secret": "JBSWY3DPEHPK3PXP", "label": "Example - alice"}
+    ]"#;
+    let skipped = import_plain(formats::Format::AndOtp, motp)?;
+    assert_eq!(
+        skipped.iter().map(Account::name).collect::<Vec<_>>(),
+        ["Example, alice"]
+    );
+
+    // FreeOTP+ secrets are signed bytes, the export has no Steam token
+    let freeotp = import_plain(
+        formats::Format::FreeOtp,
+        include_bytes!("../testdata/freeotp-plus.json"),
+    )?;
+    assert_eq!(freeotp, accounts[..3]);
+    let err = import_plain(formats::Format::FreeOtp, b"{\"tokens\": [{}]}").unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("not a FreeOTP+ export: missing field `secret`"));
+    let yandex = br#"{"tokens": [
+        {"secret": [72, 101], "label": "alice", "type": "YANDEX"},
+        {"secret": [72, 101], "issuerExt": "Example", "label": "alice"}
+    ]}"#;
+    let skipped = import_plain(formats::Format::FreeOtp, yandex)?;
+    assert_eq!(
+        skipped.iter().map(Account::name).collect::<Vec<_>>(),
+        ["Example, alice"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_kdbx() -> Result<(), Box<dyn Error>> {
+    // ChaCha20 and AES-KDF, password "foo" and an XML version 2.0 key file
+    let database = include_bytes!("../testdata/keepass.kdbx");
+    let key_file = include_bytes!("../testdata/keepass.keyx");
+    let accounts = formats::kdbx::import(database, "foo", Some(key_file))?;
+
+    // Entry history and entries without OTP are skipped, the entry title and user
+    // name stand in for a missing issuer and account name, KeeOTP "key=...&size=8&step=60&otpHashMode=SHA256"
+    let mut names = FIXTURE_NAMES;
+    names[3] = "Legacy, erin";
+    assert_fixture(&accounts, &names);
+    // Legacy "TOTP Seed" and "TOTP Settings" = "60;8"
+    assert_eq!(
+        (accounts[3].secret.as_str(), accounts[3].digits, accounts[3].period),
+        ("JBSWY3DPEHPK3PXQ", 8, 60)
+    );
+
+    // The key file is part of the composite key, a cut database is reported and never read past its end
+    for (key_file, password) in [(None, "foo"), (Some(&key_file[..]), "bar")] {
+        let err = formats::kdbx::import(database, password, key_file).unwrap_err();
+        assert!(err.to_string().contains("wrong password or key file"), "{err}");
+    }
+    for len in [8, 100, database.len() / 2] {
+        assert!(formats::kdbx::import(&database[..len], "foo", Some(key_file)).is_err());
+    }
+    let options = formats::ImportOptions {
+        key_file: Some(key_file.to_vec()),
+        ..Default::default()
+    };
+    let mut foo = |_: &str| -> Result<String, Box<dyn Error>> { Ok("foo".into()) };
+    assert_eq!(
+        formats::import(formats::Format::Kdbx, database, &mut foo, &options)?,
+        accounts
+    );
+
+    // AES-256 and Argon2id, cheap parameters keep the test fast
+    let exported = formats::kdbx::export(
+        &accounts.iter().collect::<Vec<_>>(),
+        "foo",
+        &vault::KdfParams::with_cost(256, 1, 1),
+    )?;
+    assert_eq!(formats::kdbx::import(&exported, "foo", None)?, accounts);
+    assert!(formats::kdbx::import(&exported, "bar", None).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_bitwarden() -> Result<(), Box<dyn Error>> {
+    let export = include_bytes!("../testdata/bitwarden.json");
+    let accounts = import_plain(formats::Format::Bitwarden, export)?;
+
+    // Logins without TOTP and other item types are skipped
+    assert_eq!(accounts.iter().map(Account::name).collect::<Vec<_>>(), FIXTURE_NAMES);
+
+    // A bare Base-32 secret takes the item name and user name
+    assert_eq!(
+        accounts[1],
+        Account {
+            secret: "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string(),
+            issuer: "ACME Co".to_string(),
+            account: "bob".to_string(),
+            sha: Algorithm::SHA1,
+            digits: 6,
+            period: 30,
+            otp_type: OtpType::Totp,
+            counter: 0
+        }
+    );
+    assert_eq!((accounts[2].otp_type, accounts[2].counter), (OtpType::Hotp, 5));
+    assert_eq!((accounts[3].otp_type, accounts[3].digits), (OtpType::Steam, 5));
+    assert_round_trip(formats::Format::Bitwarden, &accounts)?;
+
+    // Encrypted and cut exports are refused
+    let encrypted = br#"{"encrypted": true, "passwordProtected": true, "data": "..."}"#;
+    let err = import_plain(formats::Format::Bitwarden, encrypted).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("encrypted Bitwarden exports are not supported"));
+    let err = import_plain(formats::Format::Bitwarden, &export[..export.len() / 2]).unwrap_err();
+    assert!(err.to_string().starts_with("not a Bitwarden export: "));
+
+    // A broken otpauth URI names its item
+    let broken = br#"{"items": [{"type": 1, "name": "Example", "login": {"totp": "otpauth://totp/alice"}}]}"#;
+    let err = import_plain(formats::Format::Bitwarden, broken).unwrap_err();
+    assert!(err.to_string().starts_with("Bitwarden item `Example`: "));
+    Ok(())
+}
+
+// Uses the local gpg with a throwaway homedir, skipped when gpg is not installed
+#[cfg(unix)]
+#[test]
+fn test_password_store() -> Result<(), Box<dyn Error>> {
+    use std::process::Command;
+
+    if Command::new("gpg").arg("--version").output().is_err() {
+        eprintln!("Skipping test_password_store, gpg is not installed");
+        return Ok(());
+    }
+    let dir = std::env::temp_dir().join(format!("totp-qr-pass-{}", std::process::id()));
+    let (gnupg, store) = (dir.join("gnupg"), dir.join("store"));
+    std::fs::create_dir_all(&store)?;
+    std::os::unix::fs::DirBuilderExt::mode(&mut std::fs::DirBuilder::new(), 0o700).create(&gnupg)?;
+    let homedir = Some(gnupg.as_path());
+
+    let status = Command::new("gpg")
+        .arg("--homedir")
+        .arg(&gnupg)
+        .args(["--batch", "--quiet", "--passphrase", "", "--quick-gen-key"])
+        .args(["totp-qr test <test@example.com>", "default", "default", "never"])
+        .status()?;
+    assert!(status.success());
+    std::fs::write(store.join(".gpg-id"), "# pass init\ntest@example.com\n")?;
+
+    let accounts =
+        totp_token::get_accounts("otpauth://totp/ACME%20Co:bob?secret=GEZDGNBVGY3TQOJQ&issuer=ACME%20Co&digits=8")?;
+    let entry = password_store::entry_name("otp", &accounts[0]);
+    assert_eq!(entry, "otp/ACME Co/bob");
+    password_store::insert(&store, &entry, &accounts[0], false, homedir)?;
+    assert!(password_store::insert(&store, &entry, &accounts[0], false, homedir).is_err());
+    password_store::insert(&store, &entry, &accounts[0], true, homedir)?;
+
+    // pass-otp entries hold other lines too, a missing issuer becomes the entry path
+    let mut gpg = Command::new("gpg")
+        .arg("--homedir")
+        .arg(&gnupg)
+        .args(["--batch", "--quiet", "--encrypt", "-r", "test@example.com", "--output"])
+        .arg(store.join("github.gpg"))
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+    gpg.stdin
+        .take()
+        .ok_or("no stdin")?
+        .write_all(b"hunter2\nlogin: alice\notpauth://totp/alice?secret=JBSWY3DPEHPK3PXP\n")?;
+    assert!(gpg.wait()?.success());
+
+    assert_eq!(password_store::entries(&store)?, ["github", "otp/ACME Co/bob"]);
+    assert_eq!(
+        password_store::read_accounts(&store, "otp/ACME Co/bob", homedir)?,
+        accounts
+    );
+    let github = password_store::read_accounts(&store, "github", homedir)?;
+    assert_eq!(
+        (github[0].issuer.as_str(), github[0].account.as_str()),
+        ("github", "alice")
+    );
+
+    // An entry gpg cannot decrypt is an error of that entry alone, `pass code` skips it
+    std::fs::write(store.join("broken.gpg"), "not encrypted")?;
+    assert!(password_store::read_accounts(&store, "broken", homedir).is_err());
+
+    Command::new("gpgconf")
+        .arg("--homedir")
+        .arg(&gnupg)
+        .args(["--kill", "gpg-agent"])
+        .status()?;
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_csv() -> Result<(), Box<dyn Error>> {
+    let proton = include_bytes!("../testdata/proton-pass.csv");
+    let accounts = import_plain(formats::Format::Csv, proton)?;
+
+    // Rows without a totp are skipped, the name and email or username columns fill in the URI
+    assert_eq!(
+        accounts.iter().map(Account::name).collect::<Vec<_>>(),
+        [FIXTURE_NAMES[0], "ACME Co, Inc., bob", "LastPass style, dave"]
+    );
+    assert_eq!(
+        (accounts[1].sha, accounts[1].digits, accounts[1].period),
+        (Algorithm::SHA256, 8, 60)
+    );
+    assert_eq!(accounts[2].secret, "JBSWY3DPEHPK3PXP");
+
+    // Quoted values survive the round trip
+    let exported = formats::export(
+        formats::Format::Csv,
+        &accounts.iter().collect::<Vec<_>>(),
+        &mut no_password,
+    )?;
+    assert!(String::from_utf8_lossy(&exported).contains("\"ACME Co, Inc.\",bob,"));
+    assert_round_trip(formats::Format::Csv, &accounts)?;
+
+    // Columns mapped by header
+    let options = formats::ImportOptions {
+        csv_columns: vec![
+            formats::csv::parse_column("secret=2FA Key")?,
+            formats::csv::parse_column("account=Who")?,
+        ],
+        ..Default::default()
+    };
+    let mapped = b"Site,Who,2FA Key,Digits\nExample,alice@google.com,JBSWY3DPEHPK3PXP,6\n";
+    let mapped = formats::import(formats::Format::Csv, mapped, &mut no_password, &options)?;
+    assert_eq!(mapped[0].name(), "alice@google.com");
+    assert_eq!(mapped[0].secret, accounts[0].secret);
+    assert!(formats::csv::parse_column("color=Red").is_err());
+    let err = formats::import(formats::Format::Csv, b"Site,Who\n", &mut no_password, &options).unwrap_err();
+    assert_eq!(err.to_string(), "CSV has no column `2FA Key`");
+    let err = import_plain(formats::Format::Csv, b"name,password\nExample,hunter2\n").unwrap_err();
+    assert!(err.to_string().starts_with("CSV has no secret or otpauth column"));
+
+    // Bad rows are reported by line number
+    let bad = b"issuer,secret,digits\nExample,JBSWY3DPEHPK3PXP,6\nACME,JBSWY3DPEHPK3PXP,six\n";
+    let err = import_plain(formats::Format::Csv, bad).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "CSV line 3, column `digits`: unsupported digits `six`, expected 6 to 10"
+    );
+    // The row and column are reported, never the secret
+    let bad = b"issuer,TOTP Secret\nExample,JBSWY3DPEHPK3PXP\nACME,not base32!\n";
+    let err = import_plain(formats::Format::Csv, bad).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "CSV line 3, column `TOTP Secret`: invalid Base-32 secret"
+    );
+    let bad = b"issuer,secret\nExample,JBSWY3DPEHPK3PXP,extra\n";
+    let err = import_plain(formats::Format::Csv, bad).unwrap_err();
+    assert!(err.to_string().starts_with("CSV line 2: "));
+    Ok(())
+}
+
+#[test]
+fn test_ente_authpro() -> Result<(), Box<dyn Error>> {
+    // Trashed entries are skipped
+    let accounts = import_plain(formats::Format::Ente, include_bytes!("../testdata/ente-plain.txt"))?;
+    assert_fixture(&accounts, &FIXTURE_NAMES);
+
+    // Argon2id and libsodium secretstream
+    let encrypted = include_bytes!("../testdata/ente-encrypted.txt");
+    assert_encrypted(formats::Format::Ente, encrypted, &accounts)?;
+
+    // Authenticator Pro holds the same accounts
+    let plain = import_plain(
+        formats::Format::AuthPro,
+        include_bytes!("../testdata/authpro-plain.json"),
+    )?;
+    assert_eq!(plain, accounts);
+
+    // Argon2id and AES-256-GCM, or PBKDF2 and AES-256-CBC for legacy backups
+    let encrypted = include_bytes!("../testdata/authpro-encrypted.authpro");
+    assert_encrypted(formats::Format::AuthPro, encrypted, &accounts)?;
+    let legacy = include_bytes!("../testdata/authpro-legacy.authpro");
+    assert_encrypted(formats::Format::AuthPro, legacy, &accounts)?;
+    let err = import_with(formats::Format::AuthPro, &encrypted[..20], "foo").unwrap_err();
+    assert_eq!(err.to_string(), "Authenticator Pro backup is truncated");
+
+    // Mobile-OTP and Yandex entries are skipped, unknown algorithms are errors
+    let authenticators = |algorithm| {
+        format!(
+            r#"{{"Authenticators": [
+                {{"Type": 3, "Username": "bob", "Secret": "JBSWY3DPEHPK3PXP", "Algorithm": 0, "Digits": 6, "Period": 30}},
+                {{"Type": 5, "Username": "carol", "Secret": "JBSWY3DPEHPK3PXP", "Algorithm": 0, "Digits": 8, "Period": 30}},
+                {{"Type": 2, "Issuer": "Example", "Username": "alice", "Secret": "JBSWY3DPEHPK3PXP", "Algorithm": {algorithm}, "Digits": 6, "Period": 30}}
+            ]}}"#
+        )
+    };
+    let skipped = import_plain(formats::Format::AuthPro, authenticators(0).as_bytes())?;
+    assert_eq!(
+        skipped.iter().map(Account::name).collect::<Vec<_>>(),
+        ["Example, alice"]
+    );
+    let err = import_plain(formats::Format::AuthPro, authenticators(3).as_bytes()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Authenticator Pro entry alice: unsupported algorithm 3"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_steam() -> Result<(), Box<dyn Error>> {
+    let time = 1697590260;
+    let mut never = |_: &str| -> Result<String, Box<dyn Error>> { Err("maFiles need no password".into()) };
+    let ma_file = include_bytes!("../testdata/steam.maFile");
+    let accounts = formats::import(formats::Format::MaFile, ma_file, &mut never, &Default::default())?;
+    assert_eq!(
+        accounts,
+        [Account {
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            issuer: "Steam".to_string(),
+            account: "dave".to_string(),
+            sha: Algorithm::SHA1,
+            digits: 5,
+            period: 30,
+            otp_type: OtpType::Steam,
+            counter: 0
+        }]
+    );
+    assert_eq!(totp_token::time_token(time, &accounts[0])?, "3T78B");
+
+    // "encoder=steam" and "otpauth://steam" select the Steam encoder, URI's are written with the encoder
+    let uri = accounts[0].uri();
+    assert_eq!(
+        uri,
+        "otpauth://totp/Steam:dave?secret=JBSWY3DPEHPK3PXP&issuer=Steam&algorithm=SHA1&digits=5&period=30&encoder=steam"
+    );
+    assert_eq!(totp_token::get_accounts(&uri)?, accounts);
+    assert_eq!(
+        totp_token::get_accounts("otpauth://steam/Steam:dave?secret=JBSWY3DPEHPK3PXP&issuer=Steam")?,
+        accounts
+    );
+
+    // JSON accounts keep the type
+    let json = serde_json::to_string(&accounts)?;
+    assert!(json.contains(r#""otp_type":"steam""#));
+    assert_eq!(serde_json::from_str::<Vec<Account>>(&json)?, accounts);
+
+    assert!(otpauth_migration::to_uris(&[&accounts[0]], 1).is_err());
+    Ok(())
+}
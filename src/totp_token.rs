@@ -1,7 +1,17 @@
 use crate::otpauth_migration;
+use crate::otpauth_uri::{OtpAuthUri, OtpType};
 use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// RFC 4226 tokens have at least 6 digits, the 31 bit truncated HMAC has at most 10
+pub const DIGITS: std::ops::RangeInclusive<u32> = 6..=10;
+
+// Steam Guard tokens are 5 symbols of an alphabet without look-alike characters
+pub const STEAM_DIGITS: u32 = 5;
+const STEAM_ALPHABET: &[u8; 26] = b"23456789BCDFGHJKMNPQRTVWXY";
 
 // Create aliases
 type HmacSha1 = Hmac<sha1::Sha1>;
@@ -10,7 +20,16 @@ type HmacSha512 = Hmac<sha2::Sha512>;
 
 /// Algorithm enum holds the three standards algorithms for TOTP as per the
 /// [reference implementation](https://tools.ietf.org/html/rfc6238#appendix-A)
-enum Algorithm {
+/// and MD5, which Google Authenticator exports can carry but no token can be generated from,
+/// dynamic truncation reads up to byte 19 of a 16 byte MD5 digest
+///
+/// Names are read ignoring case, dashes and a "Hmac" prefix, e.g. "SHA256", "sha-256"
+/// and "HmacSHA256", and written as "SHA256" like the JSON of earlier versions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Algorithm {
+    MD5,
+    #[default]
     SHA1,
     SHA256,
     SHA512,
@@ -21,38 +40,149 @@ impl Algorithm {
         hmac.finalize().into_bytes().to_vec()
     }
 
-    fn sign(&self, key: &[u8], msg: &[u8]) -> Vec<u8> {
-        match self {
-            Self::SHA1 => Self::hash(HmacSha1::new_from_slice(key).unwrap(), msg),
-            Self::SHA256 => Self::hash(HmacSha256::new_from_slice(key).unwrap(), msg),
-            Self::SHA512 => Self::hash(HmacSha512::new_from_slice(key).unwrap(), msg),
-        }
+    fn sign(&self, key: &[u8], msg: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let invalid = |e| format!("HMAC key: {e}");
+        Ok(match self {
+            Self::MD5 => return Err("MD5 is not supported, its digest is too short for dynamic truncation".into()),
+            Self::SHA1 => Self::hash(HmacSha1::new_from_slice(key).map_err(invalid)?, msg),
+            Self::SHA256 => Self::hash(HmacSha256::new_from_slice(key).map_err(invalid)?, msg),
+            Self::SHA512 => Self::hash(HmacSha512::new_from_slice(key).map_err(invalid)?, msg),
+        })
     }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
 
-    fn supply(sha_name: Option<String>) -> Self {
-        match sha_name {
-            Some(name) if name.eq_ignore_ascii_case("SHA256") => Self::SHA256,
-            Some(name) if name.eq_ignore_ascii_case("SHA512") => Self::SHA512,
-            _ => Self::SHA1,
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let normalized = name.trim().to_uppercase().replace(['-', '_'], "");
+        match normalized.strip_prefix("HMAC").unwrap_or(&normalized) {
+            "SHA1" => Ok(Self::SHA1),
+            "SHA256" => Ok(Self::SHA256),
+            "SHA512" => Ok(Self::SHA512),
+            "MD5" => Ok(Self::MD5),
+            _ => Err(format!(
+                "unsupported algorithm `{name}`, expected SHA1, SHA256 or SHA512"
+            )),
         }
     }
 }
 
+impl TryFrom<String> for Algorithm {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::MD5 => "MD5",
+            Self::SHA1 => "SHA1",
+            Self::SHA256 => "SHA256",
+            Self::SHA512 => "SHA512",
+        };
+        f.write_str(name)
+    }
+}
+
+impl From<Algorithm> for String {
+    fn from(algorithm: Algorithm) -> Self {
+        algorithm.to_string()
+    }
+}
+
+/// An account field that cannot produce tokens, e.g.
+///   `account "ACME Co, bob": invalid period, must be greater than 0`
+#[derive(Debug, PartialEq)]
+pub struct InvalidAccount {
+    pub account: String,
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidAccount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "account \"{}\": invalid {}, {}",
+            self.account, self.field, self.reason
+        )
+    }
+}
+
+impl Error for InvalidAccount {}
+
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct Account {
     pub secret: String,
     pub issuer: String,
-    pub sha: String,
+    #[serde(default)]
+    pub account: String,
+    pub sha: Algorithm,
     pub digits: u32,
     pub period: u64,
+    #[serde(default)]
+    pub otp_type: OtpType,
+    #[serde(default)]
+    pub counter: u64,
 }
 
-/// Return the named parameter value fron an otpauth string
-fn uri_param(otpauth: &str, name: &str) -> Option<String> {
-    match otpauth.split(name).nth(1)?.split('&').next().map(urlencoding::decode)? {
-        Ok(s) => Some(s.into()),
-        _ => None,
+impl Account {
+    /// Return the non-empty issuer and account name joined by ", "
+    pub fn name(&self) -> String {
+        join_name(&self.issuer, &self.account)
+    }
+
+    /// Return the canonical "otpauth://" URI of the account
+    pub fn uri(&self) -> String {
+        OtpAuthUri::from(self).to_string()
     }
+
+    /// Check the fields tokens are generated from, accounts from every source go through here
+    pub fn validate(&self) -> Result<(), InvalidAccount> {
+        let invalid = |field, reason: String| InvalidAccount {
+            account: self.name(),
+            field,
+            reason,
+        };
+        match decode_secret(&self.secret) {
+            _ if self.secret.is_empty() => return Err(invalid("secret", "must not be empty".into())),
+            Some(key) if !key.is_empty() => {}
+            _ => return Err(invalid("secret", "must be Base-32".into())),
+        }
+        if self.sha == Algorithm::MD5 {
+            return Err(invalid(
+                "algorithm",
+                "MD5 is not supported, its digest is too short for dynamic truncation".into(),
+            ));
+        }
+        if self.otp_type != OtpType::Steam && !DIGITS.contains(&self.digits) {
+            return Err(invalid("digits", format!("{} is not 6 to 10", self.digits)));
+        }
+        if self.otp_type != OtpType::Hotp && self.period == 0 {
+            return Err(invalid("period", "must be greater than 0".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Decode a Base-32 secret, padding is optional
+fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    base32::decode(
+        base32::Alphabet::RFC4648 { padding: false },
+        secret.trim_end_matches('='),
+    )
+}
+
+/// Validate each account, failing on the first invalid one
+pub fn validate(accounts: Vec<Account>) -> Result<Vec<Account>, Box<dyn Error>> {
+    for account in &accounts {
+        account.validate()?;
+    }
+    Ok(accounts)
 }
 
 /// Returns a list of Account
@@ -60,56 +190,122 @@ fn uri_param(otpauth: &str, name: &str) -> Option<String> {
 /// otpauth can be 1 of 2 forms:
 ///
 ///   1. "otpauth-migration://offline" -- Protobuf of exported Accounts
-///   2. "otpauth://totp" -- String with Base-32 encoded Secret
+///   2. "otpauth://totp" or "otpauth://hotp" -- String with Base-32 encoded Secret
 pub fn get_accounts(otpauth: &str) -> Result<Vec<Account>, Box<dyn Error>> {
     match otpauth.contains("otpauth-migration://offline") {
-        true => otpauth_migration::get_accounts(otpauth),
+        true => validate(otpauth_migration::get_accounts(otpauth)?),
         false => {
-            // Secret -- required
-            let secret = uri_param(otpauth, "secret=").ok_or(format!("missing secret, otpauth = {otpauth}"))?;
+            let uri = OtpAuthUri::parse(otpauth)?;
 
-            // Issuer -- default ""
-            let issuer = uri_param(otpauth, "issuer=").unwrap_or_default();
+            // Secret -- required, validated by the parser
+            let secret = uri.param("secret").unwrap_or_default().into();
+
+            // Issuer -- default is the label issuer prefix
+            let issuer: String = uri.issuer().unwrap_or_default().into();
+
+            // Account -- label account name
+            let account = uri.account.clone();
+
+            // Period and counter are whole numbers, a bad value names the account and field
+            let number = |field, value: &str| {
+                value.parse().map_err(|_| InvalidAccount {
+                    account: join_name(&issuer, &account),
+                    field,
+                    reason: format!("{value} is not a whole number"),
+                })
+            };
 
             // Algorithm -- default "SHA1"
-            let sha = uri_param(otpauth, "algorithm=").unwrap_or("SHA1".into());
+            let sha = uri.param("algorithm").map(str::parse).transpose()?.unwrap_or_default();
 
-            // Digits -- default 6
-            let digits = match uri_param(otpauth, "digits=") {
-                Some(s) if s == "8" => 8,
+            // Digits -- default 6, Steam tokens are always 5 characters
+            let digits = match (uri.otp_type, uri.param("digits")) {
+                (OtpType::Steam, _) => STEAM_DIGITS,
+                (_, Some(s)) => parse_digits(s)?,
                 _ => 6,
             };
 
             // Period -- default 30
-            let period = match uri_param(otpauth, "period=") {
-                Some(s) => s.parse()?,
+            let period = match uri.param("period") {
+                Some(s) => number("period", s)?,
                 _ => 30,
             };
 
-            Ok(vec![Account {
+            // Counter -- required for HOTP
+            let counter = match (uri.otp_type, uri.param("counter")) {
+                (_, Some(s)) => number("counter", s)?,
+                (OtpType::Totp | OtpType::Steam, None) => 0,
+                (OtpType::Hotp, None) => return Err(format!("missing counter, otpauth = {otpauth}").into()),
+            };
+
+            validate(vec![Account {
                 secret,
                 issuer,
+                account,
                 sha,
                 digits,
                 period,
+                otp_type: uri.otp_type,
+                counter,
             }])
         }
     }
 }
 
+/// Join the non-empty issuer and account name with ", "
+fn join_name(issuer: &str, account: &str) -> String {
+    [issuer, account]
+        .into_iter()
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Parse a digit count, failing outside of DIGITS
+pub fn parse_digits(digits: &str) -> Result<u32, Box<dyn Error>> {
+    match digits.trim().parse() {
+        Ok(n) if DIGITS.contains(&n) => Ok(n),
+        _ => Err(format!("unsupported digits `{digits}`, expected 6 to 10").into()),
+    }
+}
+
+/// Encode the truncated HMAC as a Steam Guard token, least significant symbol first
+fn steam_token(mut code: u32) -> String {
+    let mut token = String::new();
+    for _ in 0..STEAM_DIGITS {
+        token.push(STEAM_ALPHABET[(code % STEAM_ALPHABET.len() as u32) as usize] as char);
+        code /= STEAM_ALPHABET.len() as u32;
+    }
+    token
+}
+
 /// Generate a time based token from the Base-32 secret and Algorithm
 pub fn time_token(time: u64, account: &Account) -> Result<String, Box<dyn Error>> {
-    let alphabet = base32::Alphabet::RFC4648 { padding: false };
-    let secret_bytes = base32::decode(alphabet, &account.secret).ok_or("base32::decode failed")?;
-    let algorithm = Algorithm::supply(Some(account.sha.to_string()));
-    let bytes = algorithm.sign(&secret_bytes, &(time / account.period).to_be_bytes());
-    match bytes.last() {
-        Some(n) => {
-            let offset = (n & 0xf) as usize;
-            let result = u32::from_be_bytes(bytes[offset..offset + 4].try_into()?);
-            let token = (result & 0x7fff_ffff) % 10_u32.pow(account.digits);
-            Ok(format!("{token:0>width$}", width = account.digits as usize))
-        }
-        _ => Err("time_token(): failed".into()),
+    account.validate()?;
+    token(time / account.period, account)
+}
+
+/// Generate a counter based token from the Base-32 secret and Algorithm
+/// as per [RFC 4226](https://tools.ietf.org/html/rfc4226#section-5.3)
+pub fn hotp_token(counter: u64, account: &Account) -> Result<String, Box<dyn Error>> {
+    account.validate()?;
+    token(counter, account)
+}
+
+/// The token of a validated account for the counter, time based tokens count periods
+fn token(counter: u64, account: &Account) -> Result<String, Box<dyn Error>> {
+    let secret_bytes = decode_secret(&account.secret).ok_or("base32::decode failed")?;
+    let bytes = account.sha.sign(&secret_bytes, &counter.to_be_bytes())?;
+
+    // Dynamic truncation
+    let offset = (bytes.last().ok_or("token(): empty HMAC")? & 0xf) as usize;
+    let window = bytes
+        .get(offset..offset + 4)
+        .ok_or("token(): HMAC too short for its truncation offset")?;
+    let result = u32::from_be_bytes(window.try_into()?) & 0x7fff_ffff;
+    if account.otp_type == OtpType::Steam {
+        return Ok(steam_token(result));
     }
+    let token = u64::from(result) % 10_u64.pow(account.digits);
+    Ok(format!("{token:0>width$}", width = account.digits as usize))
 }
@@ -0,0 +1,167 @@
+use crate::totp_token::{self, Account};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, OsRng, Payload};
+use chacha20poly1305::{AeadCore, KeyInit, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+const VERSION: u32 = 1;
+
+/// Argon2id parameters and salt used to derive the vault key from the password
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub salt: String,
+}
+
+impl KdfParams {
+    /// Argon2id with 64 MiB, 3 passes and a fresh random salt
+    pub fn new() -> Self {
+        Self::with_cost(64 * 1024, 3, 1)
+    }
+
+    /// Argon2id with the given cost and a fresh random salt
+    pub fn with_cost(memory_kib: u32, iterations: u32, parallelism: u32) -> Self {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            algorithm: "argon2id".into(),
+            memory_kib,
+            iterations,
+            parallelism,
+            salt: general_purpose::STANDARD.encode(salt),
+        }
+    }
+
+    fn derive_key(&self, password: &str) -> Result<[u8; 32], Box<dyn Error>> {
+        if self.algorithm != "argon2id" {
+            return Err(format!("unsupported vault key derivation `{}`", self.algorithm).into());
+        }
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| format!("invalid argon2id parameters: {e}"))?;
+        let salt = general_purpose::STANDARD.decode(&self.salt)?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &salt, &mut key)
+            .map_err(|e| format!("argon2id: {e}"))?;
+        Ok(key)
+    }
+
+    /// The key derivation parameters are authenticated along with the accounts
+    fn associated_data(&self, version: u32) -> String {
+        format!(
+            "totp-qr vault {version} {} {} {} {} {}",
+            self.algorithm, self.memory_kib, self.iterations, self.parallelism, self.salt
+        )
+    }
+}
+
+/// The vault file: JSON holding the key derivation parameters and the
+/// XChaCha20-Poly1305 encrypted JSON list of accounts
+#[derive(Debug, Deserialize, Serialize)]
+struct VaultFile {
+    version: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Encrypt accounts into the vault file text
+pub fn seal(accounts: &[Account], password: &str, kdf: KdfParams) -> Result<String, Box<dyn Error>> {
+    let cipher = XChaCha20Poly1305::new(&kdf.derive_key(password)?.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let plaintext = serde_json::to_vec(accounts)?;
+    let aad = kdf.associated_data(VERSION);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &plaintext,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| "vault encryption failed")?;
+
+    let vault = VaultFile {
+        version: VERSION,
+        kdf,
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+    };
+    Ok(serde_json::to_string_pretty(&vault)? + "\n")
+}
+
+/// Decrypt the accounts from the vault file text
+pub fn open(vault: &str, password: &str) -> Result<Vec<Account>, Box<dyn Error>> {
+    let vault: VaultFile = serde_json::from_str(vault).map_err(|e| format!("not a totp-qr vault: {e}"))?;
+    if vault.version != VERSION {
+        return Err(format!("unsupported vault version {}", vault.version).into());
+    }
+
+    let cipher = XChaCha20Poly1305::new(&vault.kdf.derive_key(password)?.into());
+    let nonce = general_purpose::STANDARD.decode(&vault.nonce)?;
+    if nonce.len() != 24 {
+        return Err("invalid vault nonce".into());
+    }
+    let ciphertext = general_purpose::STANDARD.decode(&vault.ciphertext)?;
+    let aad = vault.kdf.associated_data(vault.version);
+    let plaintext = cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: aad.as_bytes(),
+            },
+        )
+        .map_err(|_| "vault decryption failed, wrong password?")?;
+    totp_token::validate(serde_json::from_slice(&plaintext)?)
+}
+
+/// Read and decrypt the vault file, a path of "-" reads the vault from stdin
+pub fn read(path: &Path, password: &str) -> Result<Vec<Account>, Box<dyn Error>> {
+    let vault = match path.as_os_str() == "-" {
+        true => std::io::read_to_string(std::io::stdin()),
+        false => fs::read_to_string(path),
+    }
+    .map_err(|e| format!("could not read vault `{}`: {e}", path.display()))?;
+    open(&vault, password)
+}
+
+/// Encrypt and write the vault file, readable by the owner only
+///
+/// The vault is written to a new, uniquely named file next to the destination then
+/// renamed over it so an interrupted write never leaves a truncated vault behind
+pub fn write(path: &Path, accounts: &[Account], password: &str) -> Result<(), Box<dyn Error>> {
+    if path.as_os_str() == "-" {
+        return Err("a vault read from stdin cannot be written".into());
+    }
+    let vault = seal(accounts, password, KdfParams::new())?;
+    let name = path.file_name().ok_or("invalid vault path")?.to_string_lossy();
+    let tmp = path.with_file_name(format!(".{name}.{}.tmp", uuid::Uuid::new_v4()));
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&tmp)
+        .map_err(|e| format!("could not write vault `{}`: {e}", tmp.display()))?;
+    let written = std::io::Write::write_all(&mut file, vault.as_bytes())
+        .and_then(|_| file.sync_all())
+        .and_then(|_| fs::rename(&tmp, path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("could not write vault `{}`: {e}", path.display()).into());
+    }
+    Ok(())
+}